@@ -48,6 +48,46 @@ pub enum GameError {
     InvalidAccountCount, //11
     #[error("Passed account is not rent exempt")]
     NotRentExempt, //12
+    #[error("Requested amount exceeds the available balance")]
+    InsufficientFunds, //13
+    #[error("Player doesn't own enough keys")]
+    InsufficientKeys, //14
+    #[error("Fee or pot split doesn't add up to 100%")]
+    InvalidSplit, //15
+    #[error("Team doesn't exist in this game")]
+    InvalidTeam, //16
+    #[error("Game must have between 1 and MAX_TEAMS teams")]
+    InvalidTeamCount, //17
+    #[error("Player has reached the purchase limit for now")]
+    PurchaseLimitReached, //18
+    #[error("Airdrop tiers are invalid")]
+    InvalidAirdropConfig, //19
+    #[error("Player already has an airdrop entry waiting to be revealed")]
+    AirdropRevealPending, //1a
+    #[error("Player has no airdrop entry to reveal")]
+    NoPendingAirdrop, //1b
+    #[error("Airdrop reveal doesn't match the commitment or came too early")]
+    InvalidAirdropReveal, //1c
+    #[error("Oracle randomness is too old")]
+    StaleRandomness, //1d
+    #[error("Purchase must be a top-level instruction")]
+    NotTopLevel, //1e
+    #[error("Only one purchase per round is allowed in a transaction")]
+    MultiplePurchases, //1f
+    #[error("Name is empty, too long or uses characters other than a-z, 0-9 and single spaces")]
+    InvalidName, //20
+    #[error("Name has already been registered")]
+    NameTaken, //21
+    #[error("Players can't refer themselves")]
+    SelfReferral, //22
+    #[error("Affiliate must be referred to by a registered name or id")]
+    AffiliateNameRequired, //23
+    #[error("Affiliate doesn't hold enough keys this round")]
+    AffiliateBelowMinKeys, //24
+    #[error("Purchase can only be followed by instructions to this program or ComputeBudget")]
+    UnexpectedInstruction, //25
+    #[error("Round leader can't sell all of their keys")]
+    LeaderCannotSellAllKeys, //26
 }
 
 // --------------------------------------- so that fn return type is happy
@@ -99,6 +139,42 @@ impl PrintProgramError for GameError {
             GameError::InvalidStateType => msg!("Passed state account if of the wrong state type"),
             GameError::InvalidAccountCount => msg!("Too few or too many accounts have been passed"),
             GameError::NotRentExempt => msg!("Passed account is not rent exempt"),
+            GameError::InsufficientFunds => msg!("Requested amount exceeds the available balance"),
+            GameError::InsufficientKeys => msg!("Player doesn't own enough keys"),
+            GameError::InvalidSplit => msg!("Fee or pot split doesn't add up to 100%"),
+            GameError::InvalidTeam => msg!("Team doesn't exist in this game"),
+            GameError::InvalidTeamCount => msg!("Game must have between 1 and MAX_TEAMS teams"),
+            GameError::PurchaseLimitReached => {
+                msg!("Player has reached the purchase limit for now")
+            }
+            GameError::InvalidAirdropConfig => msg!("Airdrop tiers are invalid"),
+            GameError::AirdropRevealPending => {
+                msg!("Player already has an airdrop entry waiting to be revealed")
+            }
+            GameError::NoPendingAirdrop => msg!("Player has no airdrop entry to reveal"),
+            GameError::InvalidAirdropReveal => {
+                msg!("Airdrop reveal doesn't match the commitment or came too early")
+            }
+            GameError::StaleRandomness => msg!("Oracle randomness is too old"),
+            GameError::NotTopLevel => msg!("Purchase must be a top-level instruction"),
+            GameError::MultiplePurchases => {
+                msg!("Only one purchase per round is allowed in a transaction")
+            }
+            GameError::InvalidName => msg!(
+                "Name is empty, too long or uses characters other than a-z, 0-9 and single spaces"
+            ),
+            GameError::NameTaken => msg!("Name has already been registered"),
+            GameError::SelfReferral => msg!("Players can't refer themselves"),
+            GameError::AffiliateNameRequired => {
+                msg!("Affiliate must be referred to by a registered name or id")
+            }
+            GameError::AffiliateBelowMinKeys => {
+                msg!("Affiliate doesn't hold enough keys this round")
+            }
+            GameError::UnexpectedInstruction => msg!(
+                "Purchase can only be followed by instructions to this program or ComputeBudget"
+            ),
+            GameError::LeaderCannotSellAllKeys => msg!("Round leader can't sell all of their keys"),
         }
     }
 }
@@ -1,5 +1,11 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 
+use crate::state::{
+    AffiliateRules, AirdropConfig, AntiSnipe, EndRoundBounty, Limiter, RandomnessSource,
+    TeamConfig, TimingMode,
+};
+
+#[allow(clippy::large_enum_variant)]
 #[derive(BorshSerialize, BorshDeserialize)]
 pub enum GameInstruction {
     /// Ix0 - Initializes a new version of Fomo3D.
@@ -35,9 +41,24 @@ pub enum GameInstruction {
     /// 5 `[w]` Player's token account. Initialized.
     /// 6 `[]` SystemProgram account.
     /// 7 `[]` TokenProgram account.
-    /// The next two are passed if the user wants to credit an existing/new affiliate.
-    /// 8 `[w]` Affiliate-round state PDA. Un/Initialized.
-    /// 9 `[]` Affiliate owner's account.
+    /// 8 `[w]` Player state PDA. Un/Initialized.
+    /// The next three are passed if the user wants to credit an existing/new affiliate. Mandatory if the player
+    ///   has one listed this round, or a default one from earlier rounds (see PlayerState).
+    /// 9 `[w]` Affiliate-round state PDA. Un/Initialized.
+    /// 10 `[]` Affiliate owner's account, or the name / id state PDA of a name they registered.
+    /// 11 `[w]` Affiliate's player state PDA. Un/Initialized.
+    /// The next two are mandatory if the game pays a second affiliate tier and the affiliate has a default one listed.
+    /// 12 `[w]` (optional) Second-tier affiliate-round state PDA, for the affiliate's own default affiliate.
+    ///   Un/Initialized.
+    /// 13 `[w]` (optional) Second-tier affiliate's player state PDA. Un/Initialized.
+    /// The last ones are read by the game's randomness source and purchase guards:
+    /// 14 `[]` The game's pinned oracle result account, owned by its oracle. Passed if the game uses Oracle randomness.
+    /// 15 `[]` (optional) SlotHashes sysvar account. Mandatory if the game was initialized with strong rng,
+    ///   ignored otherwise.
+    /// 16 `[]` (optional) Instructions sysvar account. Mandatory if the game only allows top-level purchases.
+    /// 17 `[w]` (optional) Winner-round state PDA. Mandatory if the game auto-settles rounds, the round is over
+    ///   and had any buyers. The purchase then ends the round instead (buyer collects any end-round bounty).
+    /// Returns a PurchaseOutcome as return data.
     PurchaseKeys(PurchaseKeysParams),
     /// Ix3 - Withdraw any accumulated Tokens in player's name.
     /// 0 `[s]` Player's personal account.
@@ -48,11 +69,18 @@ pub enum GameInstruction {
     /// 5 `[w]` Player's token account. Initialized.
     /// 6 `[]` SystemProgram account.
     /// 7 `[]` TokenProgram account.
+    /// 8 `[w]` Player state PDA. Un/Initialized.
     WithdrawSol(WithdrawParams),
     /// Ix4 - End the current game's round. Can be run by anyone, not just the creator.
     /// 0 `[]` Game state PDA. Initialized.
     /// 1 `[w]` Round state PDA. Initialized.
-    /// 2 `[w]` Winner-round state PDA. Initialized.
+    /// 2 `[w]` Winner-round state PDA. Initialized. Optional - only needed if the round had any buyers.
+    ///   Empty rounds are closed without a winner and roll any carried-over funds into the next round.
+    /// The next four are passed if the game pays an end-round bounty (caller can be the winner):
+    /// 3 `[s]` Caller's personal account.
+    /// 4 `[w]` Caller-round state PDA. Un/Initialized.
+    /// 5 `[]` SystemProgram account.
+    /// 6 `[w]` Caller's player state PDA. Un/Initialized.
     EndRound,
     /// Ix5 - Withdraw community rewards. Can be run by whoever controls the community token wallet.
     /// 0 `[]` Game state PDA. Initialized.
@@ -62,9 +90,93 @@ pub enum GameInstruction {
     /// 4 `[s]` Community wallet owner.
     /// 5 `[]` TokenProgram account.
     WithdrawCommunityRewards(WithdrawParams),
-    /// Ix6 - Withdraw p3d rewards.
-    /// (!) Unimplemented - see explanation in processor.
+    /// Ix6 - Withdraw p3d rewards. Can be run by whoever controls the p3d token wallet.
+    /// 0 `[]` Game state PDA. Initialized.
+    /// 1 `[w]` Round state PDA. Initialized.
+    /// 2 `[w]` Token account for the round's money pot. Initialized.
+    /// 3 `[w]` P3d's token account. Initialized.
+    /// 4 `[s]` P3d wallet owner.
+    /// 5 `[]` TokenProgram account.
     WithdrawP3DRewards(WithdrawParams),
+    /// Ix7 - Purchase keys using player's unwithdrawn winnings, affiliate rewards and f3d earnings.
+    /// 0 `[s]` Player's personal account.
+    /// 1 `[]` Game state PDA. Initialized.
+    /// 2 `[w]` Round state PDA. Initialized.
+    /// 3 `[w]` Player-round state PDA. Un/Initialized.
+    /// 4 `[w]` Token account for the round's money pot. Initialized.
+    /// 5 `[]` SystemProgram account.
+    /// 6 `[]` TokenProgram account.
+    /// 7 `[w]` Player state PDA. Un/Initialized.
+    /// The next three are passed if reloading from a round other than the current one:
+    /// 8 `[]` Source round state PDA. Initialized.
+    /// 9 `[w]` Source player-round state PDA. Initialized.
+    /// 10 `[w]` Token account for the source round's money pot. Initialized.
+    /// The next three are passed if the user wants to credit an existing/new affiliate, same as in PurchaseKeys.
+    /// 11 `[w]` Affiliate-round state PDA. Un/Initialized.
+    /// 12 `[]` Affiliate owner's account, or the name / id state PDA of a name they registered.
+    /// 13 `[w]` Affiliate's player state PDA. Un/Initialized.
+    /// 14 `[w]` (optional) Second-tier affiliate-round state PDA, same as in PurchaseKeys.
+    /// 15 `[w]` (optional) Second-tier affiliate's player state PDA, same as in PurchaseKeys.
+    /// The last ones are read by the game's randomness source and purchase guards:
+    /// 16 `[]` The game's pinned oracle result account, owned by its oracle. Passed if the game uses Oracle randomness.
+    /// 17 `[]` (optional) SlotHashes sysvar account. Mandatory if the game was initialized with strong rng,
+    ///   ignored otherwise.
+    /// 18 `[]` (optional) Instructions sysvar account. Mandatory if the game only allows top-level purchases.
+    ReloadKeys(ReloadKeysParams),
+    /// Ix8 - Sell a number of keys back along the bonding curve, less the exit fee.
+    /// Keys only fetch the pot share of their curve value (the lowest of the game's teams).
+    /// Any pending f3d earnings are paid out together with the sale. The sale comes off the team total
+    /// of the player's last purchase. The round's leader can't sell all of their keys.
+    /// 0 `[s]` Player's personal account.
+    /// 1 `[]` Game state PDA. Initialized.
+    /// 2 `[w]` Round state PDA. Initialized.
+    /// 3 `[w]` Player-round state PDA. Initialized.
+    /// 4 `[w]` Token account for the round's money pot. Initialized.
+    /// 5 `[w]` Player's token account. Initialized.
+    /// 6 `[]` TokenProgram account.
+    SellKeys(SellKeysParams),
+    /// Ix9 - Reveal the secret behind a commit-reveal airdrop entry and settle the lottery.
+    /// Has to land after the commitment slot and within the game's reveal window,
+    /// otherwise the entry is forfeited.
+    /// Oracle games settle the player's pending entry here too (the secret is ignored), on the result
+    /// the oracle published right after the entry. Any later one forfeits it.
+    /// 0 `[s]` Player's personal account.
+    /// 1 `[]` Game state PDA. Initialized.
+    /// 2 `[w]` Round state PDA. Initialized.
+    /// 3 `[w]` Player-round state PDA. Initialized.
+    /// 4 `[]` SlotHashes sysvar account, or the pinned oracle result account for games using Oracle randomness.
+    RevealAirdrop(RevealAirdropParams),
+    /// Ix10 - Crank that ends the current round (if not ended yet) and starts the next one,
+    /// moving the next round share across. Same as EndRound followed by InitializeRound.
+    /// 0 `[s]` Funder account to pay any associated fees.
+    /// 1 `[w]` Game state PDA. Initialized.
+    /// 2 `[w]` Current round state PDA. Initialized.
+    /// 3 `[w]` Token account for the current round's money pot. Initialized.
+    /// 4 `[w]` Next round state PDA. Uninitialized.
+    /// 5 `[w]` Token account for the next round's money pot. Uninitialized.
+    /// 6 `[]` Token mint account. Initialized.
+    /// 7 `[]` Rent account.
+    /// 8 `[]` SystemProgram account.
+    /// 9 `[]` TokenProgram account.
+    /// The last ones are only needed if the current round hasn't been ended yet:
+    /// 10 `[w]` (optional) Winner-round state PDA. Initialized. Passed if the current round had any buyers.
+    /// The next two are passed if the game pays an end-round bounty, which goes to the funder:
+    /// 11 `[w]` (optional) Funder-round state PDA. Un/Initialized.
+    /// 12 `[w]` (optional) Funder's player state PDA. Un/Initialized.
+    EndAndAdvance,
+    /// Ix11 - Register a vanity name for the player. Names are unique per game version and
+    /// are normalized first (see `normalize_name`). The name fee goes to the current round's community share.
+    /// Each name also gets the next numeric id. Either can be used as the player's affiliate code.
+    /// 0 `[s]` Player's personal account.
+    /// 1 `[w]` Game state PDA. Initialized.
+    /// 2 `[w]` Current round state PDA. Initialized.
+    /// 3 `[w]` Name state PDA. Uninitialized.
+    /// 4 `[w]` Id state PDA for the game's next id (name count + 1). Uninitialized.
+    /// 5 `[w]` Token account for the current round's money pot. Initialized.
+    /// 6 `[w]` Player's token account. Initialized.
+    /// 7 `[]` SystemProgram account.
+    /// 8 `[]` TokenProgram account.
+    RegisterName(RegisterNameParams),
 }
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
@@ -79,12 +191,93 @@ pub struct InitGameParams {
     //time (in seconds) for max possible window
     //in original Fomo3D: 24h
     pub round_max_time: i64,
+    //gives last-second buyers a fair response window (seconds), capped by round_max_time
+    //in original Fomo3D: none
+    pub anti_snipe: AntiSnipe,
+    //whether the times above are in seconds or slots
+    //in original Fomo3D: seconds (block timestamps)
+    pub timing_mode: TimingMode,
+    //percent of the proceeds withheld when keys are sold back, distributed to f3d holders
+    pub exit_fee_percent: u8,
+    //paid to whoever ends the round, so that expired rounds don't sit around unsettled
+    //in original Fomo3D: none - the next purchase settled the round
+    pub end_round_bounty: EndRoundBounty,
+    //percent of each purchase carved out of the team's affiliate share and paid to the affiliate's
+    //own affiliate instead (eg 3 => 7% / 3% with a 10% affiliate share). Unclaimed, it goes to p3d
+    //in original Fomo3D: none - the whole affiliate share goes to the direct affiliate
+    pub second_tier_affiliate: u8,
+    //who qualifies as an affiliate - see AffiliateRules. Players can never refer themselves
+    //in original Fomo3D: anyone but the buyer, by address, id or registered name
+    pub affiliate_rules: AffiliateRules,
+    //fee for registering a player name, in base units of the game's mint
+    //in original Fomo3D: 10 finney
+    pub name_fee: u128,
+    //1 to MAX_TEAMS teams, each with their own split of purchases / the final pot
+    //in original Fomo3D: whale, bear, snek, bull - all with 2% community, 1% airdrop, 1% next round,
+    //10% affiliate + per-team f3d/p3d
+    pub teams: Vec<TeamConfig>,
+    //early round purchase limiter, amounts in base units of the game's mint
+    //in original Fomo3D: on, while pot < 100 eth, max 1 eth per player
+    pub limiter: Limiter,
+    //airdrop lottery, amounts in base units of the game's mint
+    //in original Fomo3D: min 0.1 eth, +0.1% odds per purchase, 25/50/75% for 0.1+/1+/10+ eth
+    pub airdrop: AirdropConfig,
+    //how the airdrop lottery is rolled
+    //in original Fomo3D: on-chain pseudo rng
+    pub randomness: RandomnessSource,
+    //makes the SlotHashes account mandatory for purchases, so the pseudo rng can't be
+    //reproduced from clock fields alone
+    pub strong_rng: bool,
+    //only allows purchases as top-level instructions, one per round per transaction and followed by
+    //no other programs' instructions, so that contracts can't check the airdrop outcome and revert on a loss
+    //in original Fomo3D: only a weak isHuman() check on the caller's code size
+    pub top_level_purchases: bool,
+    //lets the first purchase after the round is over end it (see EndRound) instead of failing.
+    //no keys are bought - the next round doesn't exist yet - so the buyer's tokens stay with them
+    //in original Fomo3D: always on, with the eth of that purchase credited back to the buyer
+    pub auto_settle: bool,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct PurchaseKeysParams {
     pub sol_to_be_added: u128,
     pub team: u8,
+    //keccak hash of a secret, for games using commit-reveal airdrops. All zeroes to sit the airdrop out
+    pub airdrop_commitment: [u8; 32],
+}
+
+//set as PurchaseKeys' return data, so that clients can tell a purchase that settled the round
+//(and bought nothing) apart from a regular one
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub enum PurchaseOutcome {
+    KeysPurchased { keys: u128 },
+    RoundSettled,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct ReloadKeysParams {
+    pub sol_to_be_added: u128,
+    pub team: u8,
+    //round whose unwithdrawn balance is used to pay for the keys
+    pub reload_from_round: u64,
+    //same as in PurchaseKeysParams
+    pub airdrop_commitment: [u8; 32],
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct SellKeysParams {
+    pub keys_to_be_sold: u128,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct RegisterNameParams {
+    pub name: String, //as typed - normalized by the program
+}
+
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct RevealAirdropParams {
+    //preimage of the airdrop commitment made when buying keys
+    pub secret: [u8; 32],
 }
 
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
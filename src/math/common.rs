@@ -112,6 +112,12 @@ impl TryAdd for u64 {
     }
 }
 
+impl TryCast<i64> for u64 {
+    fn try_cast(self) -> Result<i64, ProgramError> {
+        i64::try_from(self).map_err(|_| GameError::ConversionFailure.into())
+    }
+}
+
 // --------------------------------------- u128
 
 impl TrySub for u128 {
@@ -23,6 +23,27 @@ pub fn sol_received(current_keys: u128, sold_keys: u128) -> Result<u128, Program
     total_sol.try_sub(remaining_sol)
 }
 
+/// The curve is calibrated for amounts with 9 decimals (ie lamports).
+pub const CURVE_DECIMALS: u8 = 9;
+
+/// Brings an amount denominated in a mint with `decimals` to the curve's scale.
+pub fn to_curve_units(amount: u128, decimals: u8) -> Result<u128, ProgramError> {
+    if decimals >= CURVE_DECIMALS {
+        amount.try_floor_div(10u128.try_pow((decimals - CURVE_DECIMALS) as u32)?)
+    } else {
+        amount.try_mul(10u128.try_pow((CURVE_DECIMALS - decimals) as u32)?)
+    }
+}
+
+/// Brings an amount on the curve's scale back to a mint with `decimals`. Rounds down.
+pub fn from_curve_units(amount: u128, decimals: u8) -> Result<u128, ProgramError> {
+    if decimals >= CURVE_DECIMALS {
+        amount.try_mul(10u128.try_pow((decimals - CURVE_DECIMALS) as u32)?)
+    } else {
+        amount.try_floor_div(10u128.try_pow((CURVE_DECIMALS - decimals) as u32)?)
+    }
+}
+
 //constants from https://gist.github.com/ilmoi/4daad0d6e9730cc6af833c065a95b717
 //had to adjust to make them fit SOL instead of ETH - basically divided the numbers to make them smaller
 //the curve is exactly the same, no information has been lost
@@ -70,6 +91,31 @@ fn keys_to_sol(keys: u128) -> Result<u128, ProgramError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_curve_units() {
+        //9 decimals - no change
+        assert_eq!(to_curve_units(123, 9).unwrap(), 123);
+        assert_eq!(from_curve_units(123, 9).unwrap(), 123);
+        //6 decimals (eg usdc) - 1 token on either scale
+        assert_eq!(to_curve_units(1_000_000, 6).unwrap(), 1_000_000_000);
+        assert_eq!(from_curve_units(1_000_000_000, 6).unwrap(), 1_000_000);
+        assert_eq!(from_curve_units(1_999_999, 6).unwrap(), 1_999);
+        //12 decimals - precision below 9 decimals is dropped
+        assert_eq!(
+            to_curve_units(1_000_000_000_999, 12).unwrap(),
+            1_000_000_000
+        );
+        assert_eq!(
+            from_curve_units(1_000_000_000, 12).unwrap(),
+            1_000_000_000_000
+        );
+        //same purchase buys the same number of keys regardless of decimals
+        assert_eq!(
+            keys_received(0, to_curve_units(1_000_000, 6).unwrap()).unwrap(),
+            keys_received(0, LAMPORTS_PER_SOL as u128).unwrap()
+        );
+    }
+
     #[test]
     fn test_keys_received() {
         // --------------------------------------- lower bound
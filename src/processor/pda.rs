@@ -19,8 +19,9 @@ use crate::{
         util::account_exists,
     },
     state::{
-        GameState, PlayerRoundState, RoundState, StateType, GAME_STATE_SIZE,
-        PLAYER_ROUND_STATE_SIZE, ROUND_STATE_SIZE,
+        GameState, IdState, NameState, PlayerRoundState, PlayerState, RoundState, StateType,
+        GAME_STATE_SIZE, ID_STATE_SIZE, NAME_STATE_SIZE, PLAYER_ROUND_STATE_SIZE,
+        PLAYER_STATE_SIZE, ROUND_STATE_SIZE,
     },
 };
 
@@ -170,6 +171,40 @@ pub fn deserialize_or_create_player_round_state<'a>(
     }
 }
 
+/// Builds seed + verifies + deserializes/creates pda if missing
+pub fn deserialize_or_create_player_state<'a>(
+    player_state_info: &AccountInfo<'a>,
+    funder_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    player_pk: &Pubkey,
+    version: u64,
+    program_id: &Pubkey,
+) -> Result<PlayerState, ProgramError> {
+    let player_state_seed = player_state_seed(player_pk, version);
+    if account_exists(player_state_info) {
+        let player_state: PlayerState =
+            PlayerState::try_from_slice(&player_state_info.data.borrow_mut())?;
+        player_state.verify_type()?;
+        verify_pda_matches(player_state_seed.as_bytes(), program_id, player_state_info)?;
+        Ok(player_state)
+    } else {
+        create_pda_with_space(
+            player_state_seed.as_bytes(),
+            player_state_info,
+            PLAYER_STATE_SIZE,
+            program_id,
+            funder_info,
+            system_program_info,
+            program_id,
+        )?;
+        let mut player_state: PlayerState =
+            PlayerState::try_from_slice(&player_state_info.data.borrow_mut())?;
+        player_state.player_pk = *player_pk;
+        player_state.TYPE = StateType::PlayerStateTypeV1;
+        Ok(player_state)
+    }
+}
+
 /// Builds seed + verifies + deserializes pda
 pub fn deserialize_pot<'a>(
     pot_info: &AccountInfo<'a>,
@@ -221,12 +256,118 @@ pub fn create_pot<'a>(
     Account::unpack(&pot_info.data.borrow_mut()).map_err(|_| GameError::UnpackingFailure.into())
 }
 
+/// Builds seed + verifies + creates pda
+pub fn create_name_state<'a>(
+    name_state_info: &AccountInfo<'a>,
+    funder_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    name: &str,
+    version: u64,
+    program_id: &Pubkey,
+) -> Result<NameState, ProgramError> {
+    create_pda_with_space(
+        name_state_seed(name, version).as_bytes(),
+        name_state_info,
+        NAME_STATE_SIZE,
+        program_id,
+        funder_info,
+        system_program_info,
+        program_id,
+    )?;
+    NameState::try_from_slice(&name_state_info.data.borrow_mut())
+        .map_err(|_| GameError::UnpackingFailure.into())
+}
+
+/// Builds seed + verifies + creates pda
+pub fn create_id_state<'a>(
+    id_state_info: &AccountInfo<'a>,
+    funder_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    id: u64,
+    version: u64,
+    program_id: &Pubkey,
+) -> Result<IdState, ProgramError> {
+    create_pda_with_space(
+        id_state_seed(id, version).as_bytes(),
+        id_state_info,
+        ID_STATE_SIZE,
+        program_id,
+        funder_info,
+        system_program_info,
+        program_id,
+    )?;
+    IdState::try_from_slice(&id_state_info.data.borrow_mut())
+        .map_err(|_| GameError::UnpackingFailure.into())
+}
+
+/// Optional affiliate accounts passed with a purchase / reload.
+pub struct AffiliateAccounts<'r, 'a> {
+    pub round_state: &'r AccountInfo<'a>,
+    pub code: &'r AccountInfo<'a>,         //see resolve_affiliate_code
+    pub player_state: &'r AccountInfo<'a>, //holds whoever referred the affiliate
+    pub second_tier: Option<(&'r AccountInfo<'a>, &'r AccountInfo<'a>)>, //the affiliate's own affiliate's round + player state
+}
+
+/// Affiliates can be referred to by their own account, or by a name / id they registered.
+/// Verifies the name / id pda against what's stored in it + returns the affiliate's pubkey.
+pub fn resolve_affiliate_code(
+    code_info: &AccountInfo,
+    version: u64,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    if code_info.owner != program_id {
+        return Ok(*code_info.key);
+    }
+    let data = code_info.data.borrow();
+    match StateType::deserialize(&mut &data[..])? {
+        StateType::NameStateTypeV1 => {
+            let name_state = NameState::try_from_slice(&data)?;
+            let name_state_seed = name_state_seed(name_state.name(), version);
+            verify_pda_matches(name_state_seed.as_bytes(), program_id, code_info)?;
+            Ok(name_state.player_pk)
+        }
+        StateType::IdStateTypeV1 => {
+            let id_state = IdState::try_from_slice(&data)?;
+            let id_state_seed = id_state_seed(id_state.id, version);
+            verify_pda_matches(id_state_seed.as_bytes(), program_id, code_info)?;
+            Ok(id_state.player_pk)
+        }
+        _ => Err(GameError::WrongAccount.into()),
+    }
+}
+
 // --------------------------------------- private
 
 const POT_SEED: &str = "pot";
 const GAME_STATE_SEED: &str = "game";
 const ROUND_STATE_SEED: &str = "round";
 const PLAYER_ROUND_STATE_SEED: &str = "pr";
+const PLAYER_STATE_SEED: &str = "ps";
+const NAME_STATE_SEED: &str = "nm";
+const ID_STATE_SEED: &str = "id";
+
+fn player_state_seed(player_pk: &Pubkey, version: u64) -> String {
+    format!(
+        "{}{}{}",
+        PLAYER_STATE_SEED,            //2
+        &player_pk.to_string()[..12], //12 - same as the player-round state
+        version                       //8
+    )
+}
+
+fn name_state_seed(name: &str, version: u64) -> String {
+    //names can end in digits, so they go after the version, behind a separator they can't contain
+    format!(
+        "{}{}/{}",
+        NAME_STATE_SEED, //2
+        version,         //8
+        name             //MAX_NAME_LEN
+    )
+}
+
+fn id_state_seed(id: u64, version: u64) -> String {
+    format!("{}{}/{}", ID_STATE_SEED, version, id)
+}
 
 fn create_pda_with_space<'a>(
     pda_seed: &[u8],
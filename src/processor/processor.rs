@@ -4,9 +4,10 @@ use solana_program::{
     clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    native_token::LAMPORTS_PER_SOL,
+    program::set_return_data,
+    program_error::ProgramError,
     pubkey::Pubkey,
-    sysvar::Sysvar,
+    sysvar::{slot_hashes, Sysvar},
 };
 use spl_token::{
     solana_program::program_pack::Pack,
@@ -15,30 +16,43 @@ use spl_token::{
 
 use crate::{
     error::GameError,
-    instruction::{GameInstruction, InitGameParams, PurchaseKeysParams, WithdrawParams},
+    instruction::{
+        GameInstruction, InitGameParams, PurchaseKeysParams, PurchaseOutcome, RegisterNameParams,
+        ReloadKeysParams, RevealAirdropParams, SellKeysParams, WithdrawParams,
+    },
     math::{
         common::{TryAdd, TryCast, TryDiv, TryMul, TrySub},
-        curve::keys_received,
+        curve::{from_curve_units, keys_received, sol_received, to_curve_units},
     },
     processor::{
         pda::{
-            create_game_state, create_pot, create_round_state, deserialize_game_state,
-            deserialize_or_create_player_round_state, deserialize_player_round_state,
-            deserialize_pot, deserialize_round_state,
+            create_game_state, create_id_state, create_name_state, create_pot, create_round_state,
+            deserialize_game_state, deserialize_or_create_player_round_state,
+            deserialize_or_create_player_state, deserialize_player_round_state, deserialize_pot,
+            deserialize_round_state, resolve_affiliate_code, AffiliateAccounts,
+        },
+        rng::{
+            latest_slot_hash, slot_hash_after, ClockRng, CommitRevealRng, OracleRng,
+            RandomnessAccounts, RandomnessProvider,
         },
         security::{
-            verify_account_count, verify_account_ownership, verify_is_signer, verify_rent_exempt,
-            verify_round_state, verify_token_program, Owner,
+            parse_purchase_accounts, verify_account_count, verify_account_ownership,
+            verify_airdrop_config, verify_is_signer, verify_rent_exempt, verify_round_state,
+            verify_splits, verify_token_program, verify_top_level_purchase, Owner,
+            PurchaseAccounts,
         },
         spl_token::{spl_token_transfer, TokenTransferParams},
         util::{
-            account_exists, airdrop_winner, calc_new_delay, calculate_player_f3d_share,
-            time_is_out, Empty,
+            account_exists, airdrop_winner, calc_end_round_bounty, calc_new_end_time,
+            calculate_player_claimable, calculate_player_f3d_share, current_time, distribute_f3d,
+            is_zero, normalize_name, percent_of, record_player_purchase, record_player_withdrawal,
+            reveal_window_expired, roll_oracle_entry, settle_airdrop_win, time_is_out,
+            track_unclaimed_round, untrack_unclaimed_round, update_masks, Empty,
         },
     },
     state::{
-        StateType, Team, BEAR_FEE_SPLIT, BEAR_POT_SPLIT, BULL_FEE_SPLIT, BULL_POT_SPLIT,
-        SNEK_FEE_SPLIT, SNEK_POT_SPLIT, WHALE_FEE_SPLIT, WHALE_POT_SPLIT,
+        GameState, PlayerRoundState, RandomnessSource, RoundState, StateType,
+        MAX_END_ROUND_BOUNTY_PERCENT, MAX_TEAMS, PROFIT_PER_KEY_PRECISION,
     },
 };
 
@@ -64,6 +78,18 @@ impl Processor {
                 msg!("purchase keys");
                 Self::process_purchase_keys(program_id, accounts, purchase_params)
             }
+            GameInstruction::ReloadKeys(reload_params) => {
+                msg!("reload keys");
+                Self::process_reload_keys(program_id, accounts, reload_params)
+            }
+            GameInstruction::SellKeys(sell_params) => {
+                msg!("sell keys");
+                Self::process_sell_keys(program_id, accounts, sell_params)
+            }
+            GameInstruction::RevealAirdrop(reveal_params) => {
+                msg!("reveal airdrop");
+                Self::process_reveal_airdrop(program_id, accounts, reveal_params)
+            }
             GameInstruction::WithdrawSol(withdraw_params) => {
                 msg!("withdraw sol");
                 Self::process_withdraw_sol(program_id, accounts, withdraw_params)
@@ -72,6 +98,14 @@ impl Processor {
                 msg!("end round");
                 Self::process_end_round(program_id, accounts)
             }
+            GameInstruction::EndAndAdvance => {
+                msg!("end and advance");
+                Self::process_end_and_advance(program_id, accounts)
+            }
+            GameInstruction::RegisterName(name_params) => {
+                msg!("register name");
+                Self::process_register_name(program_id, accounts, name_params)
+            }
             GameInstruction::WithdrawCommunityRewards(withdraw_params) => {
                 msg!("withdraw community rewards");
                 Self::process_community_withdrawal(program_id, accounts, withdraw_params)
@@ -114,13 +148,55 @@ impl Processor {
             round_init_time,
             round_inc_time_per_key,
             round_max_time,
+            anti_snipe,
+            timing_mode,
+            exit_fee_percent,
+            end_round_bounty,
+            second_tier_affiliate,
+            affiliate_rules,
+            name_fee,
+            teams,
+            limiter,
+            airdrop,
+            randomness,
+            strong_rng,
+            top_level_purchases,
+            auto_settle,
         } = game_params;
+        if exit_fee_percent > 100
+            || anti_snipe.min_time_left > round_max_time
+            || end_round_bounty.percent > MAX_END_ROUND_BOUNTY_PERCENT
+        {
+            return Err(GameError::AboveThreshold.into());
+        }
+        if anti_snipe.window < 0 || anti_snipe.min_time_left < 0 {
+            return Err(GameError::BelowFloor.into());
+        }
+        if teams.is_empty() || teams.len() > MAX_TEAMS {
+            return Err(GameError::InvalidTeamCount.into());
+        }
+        verify_splits(&teams)?;
+        //the second tier is carved out of the affiliate share, so can't exceed it for any team
+        if teams
+            .iter()
+            .any(|team| team.fee_split.affiliate < second_tier_affiliate)
+        {
+            return Err(GameError::InvalidSplit.into());
+        }
+        verify_airdrop_config(&airdrop)?;
+        if randomness != RandomnessSource::PseudoRng && airdrop.reveal_window == 0
+            || randomness == RandomnessSource::Oracle
+                && (airdrop.oracle == Pubkey::default()
+                    || airdrop.oracle_result == Pubkey::default())
+        {
+            return Err(GameError::InvalidAirdropConfig.into());
+        }
 
         if account_exists(game_state_info) {
             return Err(GameError::AlreadyInitialized.into());
         }
 
-        Mint::unpack(&mint_info.data.borrow_mut())?; //this proves it's indeed a mint account
+        let mint = Mint::unpack(&mint_info.data.borrow_mut())?; //this proves it's indeed a mint account
         let com_wallet = Account::unpack(&com_wallet_info.data.borrow_mut())?;
         let p3d_wallet = Account::unpack(&p3d_wallet_info.data.borrow_mut())?;
         if com_wallet.mint != *mint_info.key {
@@ -142,13 +218,29 @@ impl Processor {
         game_state.round_init_time = round_init_time;
         game_state.round_inc_time_per_key = round_inc_time_per_key;
         game_state.round_max_time = round_max_time;
+        game_state.anti_snipe = anti_snipe;
+        game_state.timing_mode = timing_mode;
         game_state.version = version;
         game_state.mint = *mint_info.key;
+        game_state.decimals = mint.decimals;
         game_state.game_creator = *game_creator_info.key;
         game_state.community_wallet = *com_wallet_info.key;
         game_state.p3d_wallet = *p3d_wallet_info.key;
+        game_state.exit_fee_percent = exit_fee_percent;
+        game_state.end_round_bounty = end_round_bounty;
+        game_state.second_tier_affiliate = second_tier_affiliate;
+        game_state.affiliate_rules = affiliate_rules;
+        game_state.name_fee = name_fee;
+        game_state.limiter = limiter;
+        game_state.airdrop = airdrop;
+        game_state.randomness = randomness;
+        game_state.strong_rng = strong_rng;
+        game_state.top_level_purchases = top_level_purchases;
+        game_state.auto_settle = auto_settle;
+        game_state.team_count = teams.len() as u8;
+        game_state.teams[..teams.len()].copy_from_slice(&teams);
         game_state.TYPE = StateType::GameStateTypeV1;
-        game_state.serialize(&mut *game_state_info.data.borrow_mut())?;
+        game_state.serialize(&mut &mut game_state_info.data.borrow_mut()[..])?;
 
         Ok(())
     }
@@ -252,6 +344,8 @@ impl Processor {
             })?;
             //update current round state & verify amount matches what's in pot
             round_state.accum_sol_pot.try_self_add(move_over_amount)?;
+            //carried over funds are part of the prize pool this round
+            round_state.still_in_play.try_self_add(move_over_amount)?;
             let pot_after_transfer = deserialize_pot(
                 pot_info,
                 game_state_info,
@@ -264,20 +358,20 @@ impl Processor {
             previous_round_state
                 .withdrawn_next_round
                 .try_self_add(move_over_amount)?;
-            previous_round_state.serialize(&mut *previous_round_state_info.data.borrow_mut())?;
+            previous_round_state
+                .serialize(&mut &mut previous_round_state_info.data.borrow_mut()[..])?;
         }
 
         // --------------------------------------- update current round state
-        let clock = Clock::get()?;
         // all attributes not mentioned automatically start at 0.
         round_state.round_id = game_state.round_id;
-        round_state.start_time = clock.unix_timestamp;
+        round_state.start_time = current_time(&game_state)?;
         //to calculate end time add the initial time window specified during game initialization
         round_state.end_time = round_state.start_time.try_add(game_state.round_init_time)?;
         round_state.ended = false;
         round_state.TYPE = StateType::RoundStateTypeV1;
-        round_state.serialize(&mut *round_state_info.data.borrow_mut())?;
-        game_state.serialize(&mut *game_state_info.data.borrow_mut())?;
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+        game_state.serialize(&mut &mut game_state_info.data.borrow_mut()[..])?;
 
         Ok(())
     }
@@ -296,8 +390,7 @@ impl Processor {
         let player_token_acc_info = next_account_info(account_info_iter)?;
         let system_program_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
-        let mut affiliate_round_state_info = None;
-        let mut affiliate_owner_info = None;
+        let player_state_info = next_account_info(account_info_iter)?;
         let mut expected_owners = vec![
             Owner::SystemProgram,
             Owner::Other(*program_id),
@@ -307,25 +400,46 @@ impl Processor {
             Owner::TokenProgram,
             Owner::NativeLoader,
             Owner::BPFLoader,
+            Owner::Other(*program_id),
         ];
         //change the owner if not yet initialized
         if !account_exists(player_round_state_info) {
             expected_owners[3] = Owner::SystemProgram;
         }
-        if account_info_iter.peek().is_some() {
-            //retrieve the accounts
-            affiliate_round_state_info = Some(next_account_info(account_info_iter)?);
-            affiliate_owner_info = Some(next_account_info(account_info_iter)?);
-            //push the expected owners
+        if !account_exists(player_state_info) {
+            expected_owners[8] = Owner::SystemProgram;
+        }
+        //need to know the game's randomness source and whether the round is being settled
+        //to tell which of the optional accounts were passed
+        let (game_state, _, _) = deserialize_game_state(game_state_info, program_id)?;
+        let mut round_state = deserialize_round_state(
+            round_state_info,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        let round_is_over = time_is_out(&game_state, &round_state)?;
+        let settles_round = game_state.auto_settle && round_is_over && !round_state.ended;
+        let needs_winner = settles_round && !round_state.lead_player_pk.is_empty();
+        let PurchaseAccounts {
+            affiliate: affiliate_infos,
+            randomness: randomness_infos,
+            instructions: instructions_info,
+        } = parse_purchase_accounts(
+            account_info_iter,
+            accounts,
+            &game_state,
+            &mut expected_owners,
+            needs_winner as usize,
+            program_id,
+        )?;
+        let mut winner_state_info = None;
+        if needs_winner {
+            winner_state_info = Some(account_info_iter.next().ok_or(GameError::MissingAccount)?);
             expected_owners.push(Owner::Other(*program_id));
-            expected_owners.push(Owner::SystemProgram);
-            //change the owner if not yet initialized
-            if !account_exists(affiliate_round_state_info.unwrap()) {
-                expected_owners[8] = Owner::SystemProgram;
-            }
         }
         verify_account_ownership(accounts, &expected_owners)?;
-        verify_account_count(accounts, 8, 10)?;
+        verify_account_count(accounts, 9, 18)?;
         verify_is_signer(player_info)?;
         verify_token_program(token_program_info)?;
         verify_rent_exempt(&[
@@ -334,14 +448,200 @@ impl Processor {
             pot_info,
             player_token_acc_info,
         ])?;
+        if let Some(instructions_info) = instructions_info {
+            verify_top_level_purchase(instructions_info, program_id, round_state_info.key)?;
+        }
 
         let PurchaseKeysParams {
-            mut sol_to_be_added,
+            sol_to_be_added,
             team,
+            airdrop_commitment,
         } = purchase_params;
-        let player_pk = player_info.key;
 
-        let (game_state, _, _) = deserialize_game_state(game_state_info, program_id)?;
+        //ensure the round hasn't ended yet
+        if round_is_over {
+            if !settles_round {
+                return Err(GameError::AlreadyEnded.into());
+            }
+            // --------------------------------------- settle instead
+            //same accounts as EndRound, with the buyer collecting any bounty.
+            //the next round can only be initialized once this one has ended, so there's nowhere
+            //to direct the purchase to - no keys are bought and the buyer's tokens stay with them
+            msg!("round is over: settling it instead of buying keys");
+            let mut end_accounts = vec![game_state_info.clone(), round_state_info.clone()];
+            end_accounts.extend(winner_state_info.cloned());
+            if game_state.end_round_bounty.is_enabled() {
+                end_accounts.push(player_info.clone());
+                end_accounts.push(player_round_state_info.clone());
+                end_accounts.push(system_program_info.clone());
+                end_accounts.push(player_state_info.clone());
+            }
+            Self::process_end_round(program_id, &end_accounts)?;
+            set_return_data(&PurchaseOutcome::RoundSettled.try_to_vec()?);
+            return Ok(());
+        }
+        deserialize_pot(
+            pot_info,
+            game_state_info,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        let mut player_round_state = deserialize_or_create_player_round_state(
+            player_round_state_info,
+            player_info,
+            system_program_info,
+            player_info.key,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        let mut player_state = deserialize_or_create_player_state(
+            player_state_info,
+            player_info,
+            system_program_info,
+            player_info.key,
+            game_state.version,
+            program_id,
+        )?;
+        //a player new to the round keeps crediting their default affiliate from earlier rounds
+        if !player_round_state.has_affiliate_listed() && player_state.has_default_affiliate() {
+            player_round_state.last_affiliate_pk = player_state.default_affiliate_pk;
+        }
+        //this is not strictly necessary, but won't hurt
+        let player_token_acc = Account::unpack(&player_token_acc_info.data.borrow())?;
+        if player_token_acc.owner != *player_info.key {
+            return Err(GameError::InvalidOwner.into());
+        }
+        //no need to verify mint - the transfer below will simply fail if player acc's mint != pot mint
+
+        // --------------------------------------- buy keys
+        let keys_before = player_round_state.accum_keys;
+        let sol_added = Self::purchase_keys_core(
+            program_id,
+            &game_state,
+            &mut round_state,
+            &mut player_round_state,
+            player_info,
+            system_program_info,
+            affiliate_infos,
+            sol_to_be_added,
+            team,
+            airdrop_commitment,
+            randomness_infos,
+        )?;
+
+        // --------------------------------------- transfer funds to pot
+        spl_token_transfer(TokenTransferParams {
+            source: player_token_acc_info.clone(),
+            destination: pot_info.clone(),
+            authority: player_info.clone(), //this also enforces player_info to be a signer
+            token_program: token_program_info.clone(),
+            amount: sol_added.try_cast()?,
+            authority_signer_seeds: &[],
+        })?;
+
+        // --------------------------------------- update player profile
+        let keys = player_round_state.accum_keys.try_sub(keys_before)?;
+        record_player_purchase(
+            &mut player_state,
+            &player_round_state,
+            game_state.round_id,
+            keys,
+            sol_added,
+            team,
+        )?;
+
+        // --------------------------------------- serialize state
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+        verify_round_state(&round_state)?;
+        player_round_state.serialize(&mut &mut player_round_state_info.data.borrow_mut()[..])?;
+        player_state.serialize(&mut &mut player_state_info.data.borrow_mut()[..])?;
+
+        set_return_data(&PurchaseOutcome::KeysPurchased { keys }.try_to_vec()?);
+        Ok(())
+    }
+
+    pub fn process_reload_keys(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reload_params: ReloadKeysParams,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter().peekable();
+        let player_info = next_account_info(account_info_iter)?;
+        let game_state_info = next_account_info(account_info_iter)?;
+        let round_state_info = next_account_info(account_info_iter)?;
+        let player_round_state_info = next_account_info(account_info_iter)?;
+        let pot_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let player_state_info = next_account_info(account_info_iter)?;
+        let mut source_infos = None;
+        let mut expected_owners = vec![
+            Owner::SystemProgram,
+            Owner::Other(*program_id),
+            Owner::Other(*program_id),
+            Owner::Other(*program_id),
+            Owner::TokenProgram,
+            Owner::NativeLoader,
+            Owner::BPFLoader,
+            Owner::Other(*program_id),
+        ];
+        //change the owner if not yet initialized
+        if !account_exists(player_round_state_info) {
+            expected_owners[3] = Owner::SystemProgram;
+        }
+        if !account_exists(player_state_info) {
+            expected_owners[7] = Owner::SystemProgram;
+        }
+
+        let ReloadKeysParams {
+            sol_to_be_added,
+            team,
+            reload_from_round,
+            airdrop_commitment,
+        } = reload_params;
+
+        //need to know the current round to tell which of the optional accounts were passed
+        let (game_state, game_state_seed, game_state_bump) =
+            deserialize_game_state(game_state_info, program_id)?;
+        if reload_from_round == 0 || reload_from_round > game_state.round_id {
+            return Err(GameError::WrongAccount.into());
+        }
+        if reload_from_round != game_state.round_id {
+            let source_round_state_info = next_account_info(account_info_iter)?;
+            let source_player_round_state_info = next_account_info(account_info_iter)?;
+            let source_pot_info = next_account_info(account_info_iter)?;
+            expected_owners.push(Owner::Other(*program_id));
+            expected_owners.push(Owner::Other(*program_id));
+            expected_owners.push(Owner::TokenProgram);
+            source_infos = Some((
+                source_round_state_info,
+                source_player_round_state_info,
+                source_pot_info,
+            ));
+        }
+        let PurchaseAccounts {
+            affiliate: affiliate_infos,
+            randomness: randomness_infos,
+            instructions: instructions_info,
+        } = parse_purchase_accounts(
+            account_info_iter,
+            accounts,
+            &game_state,
+            &mut expected_owners,
+            0,
+            program_id,
+        )?;
+        verify_account_ownership(accounts, &expected_owners)?;
+        verify_account_count(accounts, 8, 19)?;
+        verify_is_signer(player_info)?;
+        verify_token_program(token_program_info)?;
+        verify_rent_exempt(&[game_state_info, round_state_info, pot_info])?;
+        if let Some(instructions_info) = instructions_info {
+            verify_top_level_purchase(instructions_info, program_id, round_state_info.key)?;
+        }
+
         let mut round_state = deserialize_round_state(
             round_state_info,
             game_state.round_id,
@@ -349,7 +649,7 @@ impl Processor {
             program_id,
         )?;
         //ensure the round hasn't ended yet
-        if time_is_out(&round_state)? {
+        if time_is_out(&game_state, &round_state)? {
             return Err(GameError::AlreadyEnded.into());
         }
         deserialize_pot(
@@ -363,69 +663,200 @@ impl Processor {
             player_round_state_info,
             player_info,
             system_program_info,
-            player_pk,
+            player_info.key,
             game_state.round_id,
             game_state.version,
             program_id,
         )?;
-        //this is not strictly necessary, but won't hurt
-        let player_token_acc = Account::unpack(&player_token_acc_info.data.borrow())?;
-        if player_token_acc.owner != *player_info.key {
-            return Err(GameError::InvalidOwner.into());
+        let mut player_state = deserialize_or_create_player_state(
+            player_state_info,
+            player_info,
+            system_program_info,
+            player_info.key,
+            game_state.version,
+            program_id,
+        )?;
+        //a player new to the round keeps crediting their default affiliate from earlier rounds
+        if !player_round_state.has_affiliate_listed() && player_state.has_default_affiliate() {
+            player_round_state.last_affiliate_pk = player_state.default_affiliate_pk;
         }
-        //no need to verify mint - the transfer below will simply fail if player acc's mint != pot mint
+        let keys_before = player_round_state.accum_keys;
+
+        // --------------------------------------- reload from the current round
+        // The funds are already sitting in the pot, so no tokens need to move - they are simply
+        // marked as withdrawn by the player and added to the pot again as a new purchase.
+        let (source_round_state_info, source_player_round_state_info, source_pot_info) =
+            match source_infos {
+                None => {
+                    let claimable = calculate_player_claimable(&player_round_state, &round_state)?;
+                    if sol_to_be_added > claimable.total()? {
+                        return Err(GameError::InsufficientFunds.into());
+                    }
+                    let sol_added = Self::purchase_keys_core(
+                        program_id,
+                        &game_state,
+                        &mut round_state,
+                        &mut player_round_state,
+                        player_info,
+                        system_program_info,
+                        affiliate_infos,
+                        sol_to_be_added,
+                        team,
+                        airdrop_commitment,
+                        randomness_infos,
+                    )?;
+                    record_player_withdrawal(&mut player_round_state, &claimable, sol_added)?;
+                    let keys = player_round_state.accum_keys.try_sub(keys_before)?;
+                    record_player_purchase(
+                        &mut player_state,
+                        &player_round_state,
+                        game_state.round_id,
+                        keys,
+                        sol_added,
+                        team,
+                    )?;
+                    player_state
+                        .lifetime_winnings
+                        .try_self_add(sol_added.min(claimable.winnings))?;
+
+                    round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+                    verify_round_state(&round_state)?;
+                    player_round_state
+                        .serialize(&mut &mut player_round_state_info.data.borrow_mut()[..])?;
+                    player_state.serialize(&mut &mut player_state_info.data.borrow_mut()[..])?;
+                    return Ok(());
+                }
+                Some(infos) => infos,
+            };
+
+        // --------------------------------------- reload from a previous round
+        let source_round_state = deserialize_round_state(
+            source_round_state_info,
+            reload_from_round,
+            game_state.version,
+            program_id,
+        )?;
+        deserialize_pot(
+            source_pot_info,
+            game_state_info,
+            reload_from_round,
+            game_state.version,
+            program_id,
+        )?;
+        let mut source_player_round_state = deserialize_player_round_state(
+            source_player_round_state_info,
+            player_info.key,
+            reload_from_round,
+            game_state.version,
+            program_id,
+        )?;
+        let claimable =
+            calculate_player_claimable(&source_player_round_state, &source_round_state)?;
+        if sol_to_be_added > claimable.total()? {
+            return Err(GameError::InsufficientFunds.into());
+        }
+        let sol_added = Self::purchase_keys_core(
+            program_id,
+            &game_state,
+            &mut round_state,
+            &mut player_round_state,
+            player_info,
+            system_program_info,
+            affiliate_infos,
+            sol_to_be_added,
+            team,
+            airdrop_commitment,
+            randomness_infos,
+        )?;
+        record_player_withdrawal(&mut source_player_round_state, &claimable, sol_added)?;
+        let keys = player_round_state.accum_keys.try_sub(keys_before)?;
+        record_player_purchase(
+            &mut player_state,
+            &player_round_state,
+            game_state.round_id,
+            keys,
+            sol_added,
+            team,
+        )?;
+        player_state
+            .lifetime_winnings
+            .try_self_add(sol_added.min(claimable.winnings))?;
+        //nothing more can be credited for a previous round, same as when withdrawing
+        if sol_added == claimable.total()? {
+            untrack_unclaimed_round(&mut player_state, reload_from_round);
+        }
+
+        //move the tokens over from the previous round's pot
+        spl_token_transfer(TokenTransferParams {
+            source: source_pot_info.clone(),
+            destination: pot_info.clone(),
+            amount: sol_added.try_cast()?,
+            authority: game_state_info.clone(),
+            authority_signer_seeds: &[game_state_seed.as_bytes(), &[game_state_bump]],
+            token_program: token_program_info.clone(),
+        })?;
+
+        // --------------------------------------- serialize state
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+        verify_round_state(&round_state)?;
+        player_round_state.serialize(&mut &mut player_round_state_info.data.borrow_mut()[..])?;
+        source_player_round_state
+            .serialize(&mut &mut source_player_round_state_info.data.borrow_mut()[..])?;
+        player_state.serialize(&mut &mut player_state_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    /// Everything that happens to round & player state when keys are bought, regardless of
+    /// where the funds come from - same as core() called by both buyCore() and reLoadCore()
+    /// in the original. Returns the amount actually spent, which can be lower than requested.
+    /// (!) Moving the tokens and serializing round / player-round state is left to the caller.
+    #[allow(clippy::too_many_arguments)]
+    fn purchase_keys_core<'a>(
+        program_id: &Pubkey,
+        game_state: &GameState,
+        round_state: &mut RoundState,
+        player_round_state: &mut PlayerRoundState,
+        player_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        affiliate_infos: Option<AffiliateAccounts<'_, 'a>>,
+        mut sol_to_be_added: u128,
+        team: u8,
+        airdrop_commitment: [u8; 32],
+        randomness_infos: RandomnessAccounts<'_, 'a>,
+    ) -> Result<u128, ProgramError> {
+        let player_pk = player_info.key;
 
         // --------------------------------------- calc variables
-        // if total pot < 100 sol, each user only allowed to contribute 1 sol total
-        sol_to_be_added = if round_state.accum_sol_pot < 100.try_mul(LAMPORTS_PER_SOL as u128)?
+        // while the pot is below the threshold, each user only allowed to contribute up to the cap
+        // in original Fomo3D: pot < 100 eth => max 1 eth per player
+        let limiter = &game_state.limiter;
+        if limiter.enabled
+            && round_state.accum_sol_pot < limiter.pot_threshold
             && player_round_state
                 .accum_sol_added
                 .try_add(sol_to_be_added)?
-                > LAMPORTS_PER_SOL as u128
+                > limiter.player_cap
         {
-            (LAMPORTS_PER_SOL as u128).try_sub(player_round_state.accum_sol_added)?
-        } else {
-            sol_to_be_added
-        };
-
-        let fee_split;
-        let player_team = match team {
-            0 => {
-                round_state
-                    .accum_sol_by_team
-                    .whale
-                    .try_self_add(sol_to_be_added)?;
-                fee_split = WHALE_FEE_SPLIT;
-                Team::Whale
+            let allowed = limiter
+                .player_cap
+                .saturating_sub(player_round_state.accum_sol_added);
+            if allowed == 0 {
+                return Err(GameError::PurchaseLimitReached.into());
             }
-            1 => {
-                round_state
-                    .accum_sol_by_team
-                    .bear
-                    .try_self_add(sol_to_be_added)?;
-                fee_split = BEAR_FEE_SPLIT;
-                Team::Bear
-            }
-            3 => {
-                round_state
-                    .accum_sol_by_team
-                    .bull
-                    .try_self_add(sol_to_be_added)?;
-                fee_split = BULL_FEE_SPLIT;
-                Team::Bull
-            }
-            _ => {
-                round_state
-                    .accum_sol_by_team
-                    .snek
-                    .try_self_add(sol_to_be_added)?;
-                fee_split = SNEK_FEE_SPLIT;
-                Team::Snek
-            }
-        };
-        let pot_percent = 86
-            .try_sub(fee_split.f3d as u128)?
-            .try_sub(fee_split.p3d as u128)?;
+            msg!(
+                "purchase clipped by limiter from {} to {}",
+                sol_to_be_added,
+                allowed
+            );
+            sol_to_be_added = allowed;
+        }
+
+        if team as usize >= game_state.team_count as usize {
+            return Err(GameError::InvalidTeam.into());
+        }
+        let fee_split = game_state.teams[team as usize].fee_split;
+        round_state.accum_sol_by_team[team as usize].try_self_add(sol_to_be_added)?;
 
         // Ensure enough lamports are sent to buy at least 1 whole key.
         // In the original game on Ethereum it was possible to purchase <1 key.
@@ -433,127 +864,263 @@ impl Processor {
         // In practice this means a min participation ticket of:
         //  - 75_000 lamports/key at the beginning of the round (when keys are cheap)
         //  - 1.7 sol/per at max capacity of the game (10bn SOL total - not actually achievable)
-        let new_keys = keys_received(round_state.accum_sol_pot, sol_to_be_added)?;
+        let new_keys = keys_received(
+            to_curve_units(round_state.curve_position, game_state.decimals)?,
+            to_curve_units(sol_to_be_added, game_state.decimals)?,
+        )?;
         if new_keys < 1 {
             return Err(GameError::BelowFloor.into());
         }
 
-        // --------------------------------------- transfer funds to pot
-        spl_token_transfer(TokenTransferParams {
-            source: player_token_acc_info.clone(),
-            destination: pot_info.clone(),
-            authority: player_info.clone(), //this also enforces player_info to be a signer
-            token_program: token_program_info.clone(),
-            amount: sol_to_be_added.try_cast()?,
-            authority_signer_seeds: &[],
-        })?;
-
         // --------------------------------------- play in airdrop lottery
-        //if they deposited > 0.1 sol, they're eligible for airdrop
-        if sol_to_be_added > (LAMPORTS_PER_SOL as u128).try_floor_div(10)? {
+        let airdrop = &game_state.airdrop;
+        //if they deposited more than the min stake, they're eligible for airdrop
+        if sol_to_be_added > airdrop.min_stake {
             let clock = Clock::get()?;
 
-            //with every extra player chance of airdrop increases by 0.1%
-            round_state.airdrop_tracker.try_self_add(1)?;
-
-            if airdrop_winner(player_pk, &clock, round_state.airdrop_tracker)? {
-                //NOTE: affiliate winnings _exclude_ contribution from this purchase, which is recorded below
-                let airdrop_to_distribute = round_state.accum_airdrop_share;
-                //3 tiers exist for airdrop
-                let prize = if sol_to_be_added > (LAMPORTS_PER_SOL as u128).try_mul(10)? {
-                    //10+ sol - win 75% of the accumulated airdrop pot
-                    airdrop_to_distribute.try_mul(75)?.try_floor_div(100)?
-                } else if sol_to_be_added > LAMPORTS_PER_SOL as u128 {
-                    //1-10 sol - win 50% of the accumulated airdrop pot
-                    airdrop_to_distribute.try_mul(50)?.try_floor_div(100)?
-                } else {
-                    //0.1-1 sol - win 25% of the accumulated airdrop pot
-                    airdrop_to_distribute.try_mul(25)?.try_floor_div(100)?
-                };
-
-                //send money
-                round_state.accum_airdrop_share.try_self_sub(prize)?;
-                player_round_state.accum_winnings.try_self_add(prize)?;
-                //reset the lottery
-                round_state.airdrop_tracker = 0;
+            //with every extra player chance of airdrop increases by odds step (in 0.1%)
+            round_state
+                .airdrop_tracker
+                .try_self_add(airdrop.odds_step)?;
+
+            let provider: Option<Box<dyn RandomnessProvider>> = match game_state.randomness {
+                RandomnessSource::PseudoRng => {
+                    let slot_hash = match randomness_infos.slot_hashes {
+                        Some(info) => Some(latest_slot_hash(&info.data.borrow())?),
+                        None => None,
+                    };
+                    Some(Box::new(ClockRng {
+                        clock: &clock,
+                        slot_hash,
+                    }))
+                }
+                RandomnessSource::Oracle => {
+                    //entries are rolled on the value published right after them, which the player can't
+                    //see coming - on their next eligible purchase (ie here) or in RevealAirdrop
+                    let oracle_rng = OracleRng::load(
+                        randomness_infos
+                            .oracle_result
+                            .ok_or(GameError::MissingAccount)?,
+                        airdrop,
+                        &clock,
+                    )?;
+                    let airdrop_odds = round_state.airdrop_tracker;
+                    if player_round_state.has_pending_airdrop()
+                        && roll_oracle_entry(
+                            &oracle_rng,
+                            airdrop,
+                            round_state,
+                            player_round_state,
+                            player_pk,
+                            &clock,
+                        )?
+                    {
+                        msg!("previous airdrop entry is still waiting on the oracle, this purchase isn't entered");
+                    } else {
+                        player_round_state.airdrop_commit_slot = clock.slot;
+                        player_round_state.airdrop_stake = sol_to_be_added;
+                        player_round_state.airdrop_odds = airdrop_odds;
+                        player_round_state.airdrop_oracle_seq = oracle_rng.seq;
+                    }
+                    None
+                }
+                RandomnessSource::CommitReveal => {
+                    //the entry is rolled later in RevealAirdrop, at the odds it was committed at
+                    if !is_zero(&airdrop_commitment) {
+                        if player_round_state.has_pending_airdrop()
+                            && !reveal_window_expired(player_round_state, airdrop, &clock)?
+                        {
+                            return Err(GameError::AirdropRevealPending.into());
+                        }
+                        player_round_state.airdrop_commitment = airdrop_commitment;
+                        player_round_state.airdrop_commit_slot = clock.slot;
+                        player_round_state.airdrop_stake = sol_to_be_added;
+                        player_round_state.airdrop_odds = round_state.airdrop_tracker;
+                    }
+                    None
+                }
+            };
+
+            if let Some(provider) = provider {
+                if airdrop_winner(|| provider.roll(player_pk), round_state.airdrop_tracker)? {
+                    //NOTE: affiliate winnings _exclude_ contribution from this purchase, which is recorded below
+                    settle_airdrop_win(airdrop, round_state, player_round_state, sol_to_be_added)?;
+                }
             }
         }
 
         // --------------------------------------- calc shares
-        //2% to community
-        let community_share = sol_to_be_added.try_floor_div(50)?;
-        //1% to future airdrops
-        let airdrop_share = sol_to_be_added.try_floor_div(100)?;
-        //1% to next round's pot
-        let next_round_share = sol_to_be_added.try_floor_div(100)?;
-        //10% to affiliate
-        let mut affiliate_share = sol_to_be_added.try_floor_div(10)?;
+        //shares as configured for the team at game init (see InitGameParams)
+        let community_share = percent_of(sol_to_be_added, fee_split.community)?;
+        let airdrop_share = percent_of(sol_to_be_added, fee_split.airdrop)?;
+        let next_round_share = percent_of(sol_to_be_added, fee_split.next_round)?;
+        let mut affiliate_share = percent_of(sol_to_be_added, fee_split.affiliate)?;
+        let second_tier_share = percent_of(sol_to_be_added, game_state.second_tier_affiliate)?;
 
         let mut p3d_share = 0;
         let mut f3d_share = 0;
 
         //if player has an affiliate listed, they MUST pass another account
-        if player_round_state.has_affiliate_listed() && affiliate_round_state_info.is_none() {
+        if player_round_state.has_affiliate_listed() && affiliate_infos.is_none() {
             return Err(GameError::MissingAccount.into());
         }
 
         //however there is a case where they don't have an affiliate but want to add one -
         //this is why we do the check again
-        if affiliate_round_state_info.is_some() && affiliate_owner_info.is_some() {
+        if let Some(affiliate_infos) = affiliate_infos {
+            let affiliate_round_state_info = affiliate_infos.round_state;
+            let affiliate_pk =
+                resolve_affiliate_code(affiliate_infos.code, game_state.version, program_id)?;
+            //(!) also keeps the affiliate's state from being the player's own, which is serialized last
+            if affiliate_pk == *player_pk {
+                return Err(GameError::SelfReferral.into());
+            }
+            //qualification rules are enforced when the affiliate is set or changed. One carried over
+            //(from earlier in the round or a previous one) can't be cleared by the player, so isn't refused
+            let is_new_affiliate = affiliate_pk != player_round_state.last_affiliate_pk;
+            let affiliate_rules = &game_state.affiliate_rules;
+            if is_new_affiliate
+                && affiliate_rules.name_required
+                && affiliate_infos.code.owner != program_id
+            {
+                return Err(GameError::AffiliateNameRequired.into());
+            }
             //doesn't matter if this the old or the new affiliate. It's the one that will be credited
             //and listed on player's profile (below)
             let mut affiliate_round_state = deserialize_or_create_player_round_state(
-                affiliate_round_state_info.unwrap(),
+                affiliate_round_state_info,
                 player_info,
                 system_program_info,
-                affiliate_owner_info.unwrap().key,
+                &affiliate_pk,
                 game_state.round_id,
                 game_state.version,
                 program_id,
             )?;
-            affiliate_round_state
-                .accum_aff
-                .try_self_add(affiliate_share)?;
-            affiliate_round_state
-                .serialize(&mut *affiliate_round_state_info.unwrap().data.borrow_mut())?;
-            //update the affiliate key going forward (may or may not have changed)
-            player_round_state.last_affiliate_pk = *affiliate_owner_info.unwrap().key;
+            let qualifies = affiliate_round_state.accum_keys >= affiliate_rules.min_keys;
+            if !qualifies && is_new_affiliate {
+                return Err(GameError::AffiliateBelowMinKeys.into());
+            }
+            if !qualifies {
+                //carried-over affiliate no longer holds enough keys - share goes unclaimed
+                msg!("affiliate below min keys: share goes to p3d");
+                affiliate_round_state
+                    .serialize(&mut &mut affiliate_round_state_info.data.borrow_mut()[..])?;
+                p3d_share.try_self_add(affiliate_share)?;
+                affiliate_share = 0;
+            } else {
+                //second tier goes to whoever referred the affiliate, as kept on their profile. Never back
+                //to the buyer - their own player-round state is serialized last and would overwrite the credit
+                let mut affiliate_player_state = deserialize_or_create_player_state(
+                    affiliate_infos.player_state,
+                    player_info,
+                    system_program_info,
+                    &affiliate_pk,
+                    game_state.version,
+                    program_id,
+                )?;
+                track_unclaimed_round(&mut affiliate_player_state, game_state.round_id);
+                affiliate_player_state
+                    .serialize(&mut &mut affiliate_infos.player_state.data.borrow_mut()[..])?;
+                let second_tier_pk = affiliate_player_state.default_affiliate_pk;
+                let pays_second_tier = second_tier_share > 0
+                    && affiliate_player_state.has_default_affiliate()
+                    && second_tier_pk != *player_pk;
+                affiliate_round_state
+                    .accum_aff
+                    .try_self_add(affiliate_share.try_sub(second_tier_share)?)?;
+                affiliate_round_state
+                    .serialize(&mut &mut affiliate_round_state_info.data.borrow_mut()[..])?;
+                //update the affiliate key going forward (may or may not have changed)
+                player_round_state.last_affiliate_pk = affiliate_pk;
+
+                if pays_second_tier {
+                    let (second_tier_round_state_info, second_tier_player_state_info) =
+                        affiliate_infos
+                            .second_tier
+                            .ok_or(GameError::MissingAccount)?;
+                    //(!) deserialized after the affiliate's state is written, in case it's the same account
+                    let mut second_tier_round_state = deserialize_or_create_player_round_state(
+                        second_tier_round_state_info,
+                        player_info,
+                        system_program_info,
+                        &second_tier_pk,
+                        game_state.round_id,
+                        game_state.version,
+                        program_id,
+                    )?;
+                    second_tier_round_state
+                        .accum_aff
+                        .try_self_add(second_tier_share)?;
+                    second_tier_round_state
+                        .serialize(&mut &mut second_tier_round_state_info.data.borrow_mut()[..])?;
+                    let mut second_tier_player_state = deserialize_or_create_player_state(
+                        second_tier_player_state_info,
+                        player_info,
+                        system_program_info,
+                        &second_tier_pk,
+                        game_state.version,
+                        program_id,
+                    )?;
+                    track_unclaimed_round(&mut second_tier_player_state, game_state.round_id);
+                    second_tier_player_state
+                        .serialize(&mut &mut second_tier_player_state_info.data.borrow_mut()[..])?;
+                } else {
+                    //unclaimed second tier goes to p3d, same as an unclaimed affiliate share
+                    p3d_share.try_self_add(second_tier_share)?;
+                    affiliate_share.try_self_sub(second_tier_share)?;
+                }
+            }
         } else {
             p3d_share.try_self_add(affiliate_share)?;
             affiliate_share = 0;
         }
 
-        p3d_share.try_self_add(
-            sol_to_be_added
-                .try_mul(fee_split.p3d as u128)?
-                .try_floor_div(100)?,
-        )?;
-        f3d_share.try_self_add(
-            sol_to_be_added
-                .try_mul(fee_split.f3d as u128)?
-                .try_floor_div(100)?,
-        )?;
+        p3d_share.try_self_add(percent_of(sol_to_be_added, fee_split.p3d)?)?;
+        f3d_share.try_self_add(percent_of(sol_to_be_added, fee_split.f3d)?)?;
 
-        let still_in_play = sol_to_be_added
+        let mut still_in_play = sol_to_be_added
             .try_sub(community_share)?
             .try_sub(airdrop_share)?
             .try_sub(next_round_share)?
             .try_sub(affiliate_share)?
             .try_sub(p3d_share)?
             .try_sub(f3d_share)?;
-        assert!(still_in_play >= sol_to_be_added.try_mul(pot_percent)?.try_floor_div(100)?);
+        assert!(still_in_play >= percent_of(sol_to_be_added, fee_split.pot)?);
 
-        // --------------------------------------- serialize round state
+        // --------------------------------------- update round state
         //update leader
         round_state.lead_player_pk = *player_pk;
-        round_state.lead_player_team = player_team;
+        round_state.lead_player_team = team;
         //update timer
-        round_state
-            .end_time
-            .try_self_add(calc_new_delay(new_keys, &game_state)? as i64)?;
+        let now = current_time(game_state)?;
+        let time_left = round_state.end_time.try_sub(now)?;
+        round_state.end_time = calc_new_end_time(new_keys, round_state, game_state, now)?;
+        //last-second purchases get a fair response window, however few keys they buy
+        let anti_snipe = &game_state.anti_snipe;
+        if anti_snipe.window > 0 && time_left <= anti_snipe.window {
+            let min_end_time = now.try_add(anti_snipe.min_time_left)?;
+            if round_state.end_time < min_end_time {
+                msg!(
+                    "anti-snipe: round extended to {}{} left",
+                    anti_snipe.min_time_left,
+                    game_state.timing_mode.unit()
+                );
+                round_state.end_time = min_end_time;
+            }
+        }
         //update totals
         round_state.accum_keys.try_self_add(new_keys)?;
         round_state.accum_sol_pot.try_self_add(sol_to_be_added)?;
+        round_state.curve_position.try_self_add(sol_to_be_added)?;
+        player_round_state.accum_keys.try_self_add(new_keys)?;
+        player_round_state
+            .accum_sol_added
+            .try_self_add(sol_to_be_added)?;
+        player_round_state.last_team = team;
+        //distribute f3d share among key holders (incl. the new keys), any dust stays in play
+        let f3d_dust = update_masks(round_state, player_round_state, f3d_share, new_keys)?;
+        f3d_share.try_self_sub(f3d_dust)?;
+        still_in_play.try_self_add(f3d_dust)?;
         //distribute shares
         round_state
             .accum_community_share
@@ -568,25 +1135,14 @@ impl Processor {
         round_state.accum_p3d_share.try_self_add(p3d_share)?;
         round_state.accum_f3d_share.try_self_add(f3d_share)?;
         round_state.still_in_play.try_self_add(still_in_play)?;
-        round_state.serialize(&mut *round_state_info.data.borrow_mut())?;
-
-        verify_round_state(&round_state)?;
-
-        // --------------------------------------- serialize player-round state
-        //update totals
-        player_round_state.accum_keys.try_self_add(new_keys)?;
-        player_round_state
-            .accum_sol_added
-            .try_self_add(sol_to_be_added)?;
-        player_round_state.serialize(&mut *player_round_state_info.data.borrow_mut())?;
 
-        Ok(())
+        Ok(sol_to_be_added)
     }
 
-    pub fn process_withdraw_sol(
+    pub fn process_sell_keys(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        withdraw_params: WithdrawParams,
+        sell_params: SellKeysParams,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let player_info = next_account_info(account_info_iter)?;
@@ -595,7 +1151,6 @@ impl Processor {
         let player_round_state_info = next_account_info(account_info_iter)?;
         let pot_info = next_account_info(account_info_iter)?;
         let player_token_acc_info = next_account_info(account_info_iter)?;
-        let system_program_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
         let expected_owners = [
             Owner::SystemProgram,
@@ -604,11 +1159,10 @@ impl Processor {
             Owner::Other(*program_id),
             Owner::TokenProgram,
             Owner::TokenProgram,
-            Owner::NativeLoader,
             Owner::BPFLoader,
         ];
         verify_account_ownership(accounts, &expected_owners)?;
-        verify_account_count(accounts, 8, 8)?;
+        verify_account_count(accounts, 7, 7)?;
         verify_is_signer(player_info)?;
         verify_token_program(token_program_info)?;
         verify_rent_exempt(&[
@@ -619,29 +1173,31 @@ impl Processor {
             player_token_acc_info,
         ])?;
 
-        let WithdrawParams { withdraw_for_round } = withdraw_params;
+        let SellKeysParams { keys_to_be_sold } = sell_params;
 
         let (game_state, game_state_seed, game_state_bump) =
             deserialize_game_state(game_state_info, program_id)?;
-        let round_state = deserialize_round_state(
+        let mut round_state = deserialize_round_state(
             round_state_info,
-            withdraw_for_round,
+            game_state.round_id,
             game_state.version,
             program_id,
         )?;
+        //once the round is over keys are locked in
+        if time_is_out(&game_state, &round_state)? {
+            return Err(GameError::AlreadyEnded.into());
+        }
         deserialize_pot(
             pot_info,
             game_state_info,
-            withdraw_for_round,
+            game_state.round_id,
             game_state.version,
             program_id,
         )?;
-        let mut player_round_state = deserialize_or_create_player_round_state(
+        let mut player_round_state = deserialize_player_round_state(
             player_round_state_info,
-            player_info,
-            system_program_info,
             player_info.key,
-            withdraw_for_round,
+            game_state.round_id,
             game_state.version,
             program_id,
         )?;
@@ -650,70 +1206,322 @@ impl Processor {
         if player_token_acc.owner != *player_info.key {
             return Err(GameError::InvalidOwner.into());
         }
-        //no need to verify mint - the transfer below will simply fail if player acc's mint != pot mint
 
-        // --------------------------------------- calc withdrawal amounts
-        // No, you don't need to wait for round end to withdraw winnings.
-        // Grand prize will not have been added yet,
-        // and airdrop lottery winnings should be available to user to withdraw.
-        let winnings_to_withdraw = player_round_state
-            .accum_winnings
-            .try_sub(player_round_state.withdrawn_winnings)?;
-        let aff_to_withdraw = player_round_state
-            .accum_aff
-            .try_sub(player_round_state.withdrawn_aff)?;
-        let f3d_to_withdraw = calculate_player_f3d_share(
-            player_round_state.accum_keys,
-            round_state.accum_keys,
-            round_state.accum_f3d_share,
-        )?
-        .try_sub(player_round_state.withdrawn_f3d)?;
-        let total_to_withdraw = winnings_to_withdraw
-            .try_add(aff_to_withdraw)?
-            .try_add(f3d_to_withdraw)?;
+        // --------------------------------------- calc proceeds
+        if keys_to_be_sold < 1 {
+            return Err(GameError::BelowFloor.into());
+        }
+        if keys_to_be_sold > player_round_state.accum_keys {
+            return Err(GameError::InsufficientKeys.into());
+        }
+        //the leader stays in the running for the grand prize, so has to keep some skin in the game
+        if *player_info.key == round_state.lead_player_pk
+            && keys_to_be_sold == player_round_state.accum_keys
+        {
+            return Err(GameError::LeaderCannotSellAllKeys.into());
+        }
+        //the price is taken off the curve at the current key supply
+        let curve_value = from_curve_units(
+            sol_received(round_state.accum_keys, keys_to_be_sold)?,
+            game_state.decimals,
+        )?;
+        //only the pot share of a purchase stays behind the keys - pay out the lowest of the teams',
+        //so that buying and selling straight back can't take out more than was put in
+        let pot_percent = game_state.teams[..game_state.team_count as usize]
+            .iter()
+            .map(|team| team.fee_split.pot)
+            .min()
+            .unwrap_or(0);
+        let sale_proceeds = percent_of(curve_value, pot_percent)?;
+        //can only be paid out of funds that aren't already owed to someone
+        if sale_proceeds > round_state.still_in_play {
+            return Err(GameError::InsufficientFunds.into());
+        }
+        let mut exit_fee = percent_of(sale_proceeds, game_state.exit_fee_percent)?;
+        let sol_to_player = sale_proceeds.try_sub(exit_fee)?;
+        //f3d earned on the keys so far is settled now, as the mask is reset below
+        let f3d_to_withdraw = calculate_player_f3d_share(&player_round_state, &round_state)?;
+
+        // --------------------------------------- update round & player state
+        round_state.accum_keys.try_self_sub(keys_to_be_sold)?;
+        round_state.curve_position.try_self_sub(curve_value)?;
+        //(!) the team total can hold less than the curve value if the player switched teams -
+        //keys are only tracked against their last one
+        let team_total = &mut round_state.accum_sol_by_team[player_round_state.last_team as usize];
+        *team_total = team_total.saturating_sub(curve_value);
+        round_state.still_in_play.try_self_sub(sale_proceeds)?;
+        round_state.accum_sol_pot.try_self_sub(sol_to_player)?;
+        player_round_state
+            .accum_keys
+            .try_self_sub(keys_to_be_sold)?;
+        //exit fee goes to the remaining key holders, any dust stays in play
+        let f3d_dust = distribute_f3d(&mut round_state, exit_fee)?;
+        exit_fee.try_self_sub(f3d_dust)?;
+        round_state.accum_f3d_share.try_self_add(exit_fee)?;
+        round_state.still_in_play.try_self_add(f3d_dust)?;
+        //seller doesn't earn on the exit fee, nor on anything before it
+        player_round_state.f3d_mask = round_state
+            .profit_per_key
+            .try_mul(player_round_state.accum_keys)?
+            .try_floor_div(PROFIT_PER_KEY_PRECISION)?;
+        player_round_state
+            .withdrawn_f3d
+            .try_self_add(f3d_to_withdraw)?;
 
         // --------------------------------------- transfer tokens
-        if total_to_withdraw == 0 {
-            return Ok(());
-        }
         spl_token_transfer(TokenTransferParams {
             source: pot_info.clone(),
             destination: player_token_acc_info.clone(),
-            amount: total_to_withdraw.try_cast()?,
+            amount: sol_to_player.try_add(f3d_to_withdraw)?.try_cast()?,
             authority: game_state_info.clone(),
             authority_signer_seeds: &[game_state_seed.as_bytes(), &[game_state_bump]],
             token_program: token_program_info.clone(),
         })?;
 
-        // --------------------------------------- update player state
-        player_round_state
-            .withdrawn_aff
-            .try_self_add(aff_to_withdraw)?;
-        player_round_state
-            .withdrawn_winnings
-            .try_self_add(winnings_to_withdraw)?;
-        player_round_state
-            .withdrawn_f3d
-            .try_self_add(f3d_to_withdraw)?;
-        player_round_state.serialize(&mut *player_round_state_info.data.borrow_mut())?;
+        // --------------------------------------- serialize state
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+        verify_round_state(&round_state)?;
+        player_round_state.serialize(&mut &mut player_round_state_info.data.borrow_mut()[..])?;
 
         Ok(())
     }
 
-    pub fn process_end_round(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    pub fn process_reveal_airdrop(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reveal_params: RevealAirdropParams,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
+        let player_info = next_account_info(account_info_iter)?;
         let game_state_info = next_account_info(account_info_iter)?;
         let round_state_info = next_account_info(account_info_iter)?;
-        let winner_state_info = next_account_info(account_info_iter)?;
+        let player_round_state_info = next_account_info(account_info_iter)?;
+        let randomness_info = next_account_info(account_info_iter)?;
+
+        let (game_state, _, _) = deserialize_game_state(game_state_info, program_id)?;
+        let uses_oracle = game_state.randomness == RandomnessSource::Oracle;
         let expected_owners = [
+            Owner::SystemProgram,
             Owner::Other(*program_id),
             Owner::Other(*program_id),
             Owner::Other(*program_id),
+            if uses_oracle {
+                Owner::Other(game_state.airdrop.oracle)
+            } else {
+                Owner::Sysvar
+            },
         ];
         verify_account_ownership(accounts, &expected_owners)?;
-        verify_account_count(accounts, 3, 3)?;
-        verify_rent_exempt(&[game_state_info, round_state_info, winner_state_info])?;
+        verify_account_count(accounts, 5, 5)?;
+        verify_is_signer(player_info)?;
+        let randomness_pk = if uses_oracle {
+            game_state.airdrop.oracle_result
+        } else {
+            slot_hashes::id()
+        };
+        if *randomness_info.key != randomness_pk {
+            return Err(GameError::WrongAccount.into());
+        }
 
+        let RevealAirdropParams { secret } = reveal_params;
+
+        let mut round_state = deserialize_round_state(
+            round_state_info,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        let mut player_round_state = deserialize_player_round_state(
+            player_round_state_info,
+            player_info.key,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        if !player_round_state.has_pending_airdrop() {
+            return Err(GameError::NoPendingAirdrop.into());
+        }
+
+        // --------------------------------------- roll
+        let airdrop = &game_state.airdrop;
+        let clock = Clock::get()?;
+        if uses_oracle {
+            let oracle_rng = OracleRng::load(randomness_info, airdrop, &clock)?;
+            if roll_oracle_entry(
+                &oracle_rng,
+                airdrop,
+                &mut round_state,
+                &mut player_round_state,
+                player_info.key,
+                &clock,
+            )? {
+                msg!("oracle hasn't published the entry's result yet, try again later");
+                return Err(GameError::InvalidAirdropReveal.into());
+            }
+        } else if reveal_window_expired(&player_round_state, airdrop, &clock)? {
+            msg!("reveal window has expired, airdrop entry forfeited");
+        } else {
+            if solana_program::keccak::hash(&secret).to_bytes()
+                != player_round_state.airdrop_commitment
+            {
+                return Err(GameError::InvalidAirdropReveal.into());
+            }
+            //the slot after the commitment has to be in before the entry can be rolled
+            let slot_hash = slot_hash_after(
+                &randomness_info.data.borrow(),
+                player_round_state.airdrop_commit_slot,
+            )?
+            .ok_or_else(|| {
+                msg!("no slot hash since the commitment yet, try again later");
+                GameError::InvalidAirdropReveal
+            })?;
+
+            let provider = CommitRevealRng { secret, slot_hash };
+            if airdrop_winner(
+                || provider.roll(player_info.key),
+                player_round_state.airdrop_odds,
+            )? {
+                let stake = player_round_state.airdrop_stake;
+                settle_airdrop_win(airdrop, &mut round_state, &mut player_round_state, stake)?;
+            }
+        }
+        player_round_state.clear_pending_airdrop();
+
+        // --------------------------------------- serialize state
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+        verify_round_state(&round_state)?;
+        player_round_state.serialize(&mut &mut player_round_state_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    pub fn process_withdraw_sol(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        withdraw_params: WithdrawParams,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let player_info = next_account_info(account_info_iter)?;
+        let game_state_info = next_account_info(account_info_iter)?;
+        let round_state_info = next_account_info(account_info_iter)?;
+        let player_round_state_info = next_account_info(account_info_iter)?;
+        let pot_info = next_account_info(account_info_iter)?;
+        let player_token_acc_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let player_state_info = next_account_info(account_info_iter)?;
+        let mut expected_owners = [
+            Owner::SystemProgram,
+            Owner::Other(*program_id),
+            Owner::Other(*program_id),
+            Owner::Other(*program_id),
+            Owner::TokenProgram,
+            Owner::TokenProgram,
+            Owner::NativeLoader,
+            Owner::BPFLoader,
+            Owner::Other(*program_id),
+        ];
+        //change the owner if not yet initialized
+        if !account_exists(player_state_info) {
+            expected_owners[8] = Owner::SystemProgram;
+        }
+        verify_account_ownership(accounts, &expected_owners)?;
+        verify_account_count(accounts, 9, 9)?;
+        verify_is_signer(player_info)?;
+        verify_token_program(token_program_info)?;
+        verify_rent_exempt(&[
+            game_state_info,
+            round_state_info,
+            player_round_state_info,
+            pot_info,
+            player_token_acc_info,
+        ])?;
+
+        let WithdrawParams { withdraw_for_round } = withdraw_params;
+
+        let (game_state, game_state_seed, game_state_bump) =
+            deserialize_game_state(game_state_info, program_id)?;
+        let round_state = deserialize_round_state(
+            round_state_info,
+            withdraw_for_round,
+            game_state.version,
+            program_id,
+        )?;
+        deserialize_pot(
+            pot_info,
+            game_state_info,
+            withdraw_for_round,
+            game_state.version,
+            program_id,
+        )?;
+        let mut player_round_state = deserialize_or_create_player_round_state(
+            player_round_state_info,
+            player_info,
+            system_program_info,
+            player_info.key,
+            withdraw_for_round,
+            game_state.version,
+            program_id,
+        )?;
+        let mut player_state = deserialize_or_create_player_state(
+            player_state_info,
+            player_info,
+            system_program_info,
+            player_info.key,
+            game_state.version,
+            program_id,
+        )?;
+        //verify the destination token account actually belongs to the player
+        let player_token_acc = Account::unpack(&player_token_acc_info.data.borrow())?;
+        if player_token_acc.owner != *player_info.key {
+            return Err(GameError::InvalidOwner.into());
+        }
+        //no need to verify mint - the transfer below will simply fail if player acc's mint != pot mint
+
+        // --------------------------------------- calc withdrawal amounts
+        // No, you don't need to wait for round end to withdraw winnings.
+        // Grand prize will not have been added yet,
+        // and airdrop lottery winnings should be available to user to withdraw.
+        let claimable = calculate_player_claimable(&player_round_state, &round_state)?;
+        let total_to_withdraw = claimable.total()?;
+
+        // --------------------------------------- transfer tokens
+        if total_to_withdraw > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: pot_info.clone(),
+                destination: player_token_acc_info.clone(),
+                amount: total_to_withdraw.try_cast()?,
+                authority: game_state_info.clone(),
+                authority_signer_seeds: &[game_state_seed.as_bytes(), &[game_state_bump]],
+                token_program: token_program_info.clone(),
+            })?;
+
+            // --------------------------------------- update player state
+            record_player_withdrawal(&mut player_round_state, &claimable, total_to_withdraw)?;
+            player_round_state
+                .serialize(&mut &mut player_round_state_info.data.borrow_mut()[..])?;
+        }
+
+        // --------------------------------------- update player profile
+        player_state
+            .lifetime_winnings
+            .try_self_add(claimable.winnings)?;
+        //nothing more can be credited to the player once the round has ended
+        if round_state.ended {
+            untrack_unclaimed_round(&mut player_state, withdraw_for_round);
+        }
+        player_state.serialize(&mut &mut player_state_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    pub fn process_end_round(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let game_state_info = next_account_info(account_info_iter)?;
+        let round_state_info = next_account_info(account_info_iter)?;
+        let mut expected_owners = vec![Owner::Other(*program_id), Owner::Other(*program_id)];
+        //need to know whether the round had any buyers and whether the game pays a bounty
+        //to tell which of the optional accounts were passed
         let (game_state, _, _) = deserialize_game_state(game_state_info, program_id)?;
         let mut round_state = deserialize_round_state(
             round_state_info,
@@ -721,11 +1529,111 @@ impl Processor {
             game_state.version,
             program_id,
         )?;
+        let mut winner_state_info = None;
+        if !round_state.lead_player_pk.is_empty() {
+            winner_state_info = Some(account_info_iter.next().ok_or(GameError::MissingAccount)?);
+            expected_owners.push(Owner::Other(*program_id));
+        }
+        let mut bounty_infos = None;
+        if game_state.end_round_bounty.is_enabled() {
+            let caller_info = account_info_iter.next().ok_or(GameError::MissingAccount)?;
+            let caller_round_state_info =
+                account_info_iter.next().ok_or(GameError::MissingAccount)?;
+            let system_program_info = account_info_iter.next().ok_or(GameError::MissingAccount)?;
+            let caller_state_info = account_info_iter.next().ok_or(GameError::MissingAccount)?;
+            expected_owners.push(Owner::SystemProgram);
+            //change the owner if not yet initialized
+            expected_owners.push(if account_exists(caller_round_state_info) {
+                Owner::Other(*program_id)
+            } else {
+                Owner::SystemProgram
+            });
+            expected_owners.push(Owner::NativeLoader);
+            expected_owners.push(if account_exists(caller_state_info) {
+                Owner::Other(*program_id)
+            } else {
+                Owner::SystemProgram
+            });
+            bounty_infos = Some((
+                caller_info,
+                caller_round_state_info,
+                system_program_info,
+                caller_state_info,
+            ));
+        }
+        verify_account_count(accounts, expected_owners.len(), expected_owners.len())?;
+        verify_account_ownership(accounts, &expected_owners)?;
+        verify_rent_exempt(&[game_state_info, round_state_info])?;
 
-        if !time_is_out(&round_state)? {
+        if round_state.ended {
+            return Err(GameError::AlreadyEnded.into());
+        }
+        if !time_is_out(&game_state, &round_state)? {
             return Err(GameError::NotYetEnded.into());
         }
 
+        // --------------------------------------- pay the bounty
+        //comes out of what's still in play, before the pot is split
+        let mut winner_bounty = 0;
+        if let Some((
+            caller_info,
+            caller_round_state_info,
+            system_program_info,
+            caller_state_info,
+        )) = bounty_infos
+        {
+            verify_is_signer(caller_info)?;
+            let bounty =
+                calc_end_round_bounty(&game_state.end_round_bounty, round_state.still_in_play)?;
+            round_state.still_in_play.try_self_sub(bounty)?;
+            round_state.bounty_paid.try_self_add(bounty)?;
+            if winner_state_info.is_some_and(|info| info.key == caller_round_state_info.key) {
+                //same account passed twice - credited together with the grand prize below
+                if *caller_info.key != round_state.lead_player_pk {
+                    return Err(GameError::WrongAccount.into());
+                }
+                winner_bounty = bounty;
+            } else {
+                let mut caller_round_state = deserialize_or_create_player_round_state(
+                    caller_round_state_info,
+                    caller_info,
+                    system_program_info,
+                    caller_info.key,
+                    game_state.round_id,
+                    game_state.version,
+                    program_id,
+                )?;
+                caller_round_state.accum_winnings.try_self_add(bounty)?;
+                caller_round_state
+                    .serialize(&mut &mut caller_round_state_info.data.borrow_mut()[..])?;
+            }
+            let mut caller_state = deserialize_or_create_player_state(
+                caller_state_info,
+                caller_info,
+                system_program_info,
+                caller_info.key,
+                game_state.version,
+                program_id,
+            )?;
+            track_unclaimed_round(&mut caller_state, game_state.round_id);
+            caller_state.serialize(&mut &mut caller_state_info.data.borrow_mut()[..])?;
+        }
+
+        // --------------------------------------- empty round
+        //nobody to pay out - whatever was carried over goes on to the next round
+        if winner_state_info.is_none() {
+            round_state.ended = true;
+            round_state
+                .accum_next_round_share
+                .try_self_add(round_state.still_in_play)?;
+            round_state.still_in_play = 0;
+            round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+            verify_round_state(&round_state)?;
+            return Ok(());
+        }
+
+        let winner_state_info = winner_state_info.unwrap();
+        verify_rent_exempt(&[winner_state_info])?;
         let mut player_round_state = deserialize_player_round_state(
             winner_state_info,
             &round_state.lead_player_pk,
@@ -736,47 +1644,33 @@ impl Processor {
 
         // --------------------------------------- calc shares
         let to_be_divided = round_state.still_in_play;
-        if to_be_divided == 0 || round_state.lead_player_pk.is_empty() {
-            return Ok(());
-        }
 
-        let pot_split = match round_state.lead_player_team {
-            Team::Whale => WHALE_POT_SPLIT,
-            Team::Bear => BEAR_POT_SPLIT,
-            Team::Snek => SNEK_POT_SPLIT,
-            Team::Bull => BULL_POT_SPLIT,
-        };
-        let next_round_percent = 50
-            .try_sub(pot_split.p3d as u128)?
-            .try_sub(pot_split.f3d as u128)?;
-
-        //2% to community
-        let community_share = to_be_divided.try_floor_div(50)?;
-
-        //p3d/f3d/next round according to team (always adds up to 50%)
-        let p3d_share = to_be_divided
-            .try_mul(pot_split.p3d as u128)?
-            .try_floor_div(100)?;
-        let f3d_share = to_be_divided
-            .try_mul(pot_split.f3d as u128)?
-            .try_floor_div(100)?;
-        let next_round_share = to_be_divided
-            .try_mul(next_round_percent)?
-            .try_floor_div(100)?;
-
-        //remaining 48% + dust to winner
+        let pot_split = game_state.teams[round_state.lead_player_team as usize].pot_split;
+
+        //shares as configured for the winning team at game init (see InitGameParams)
+        let community_share = percent_of(to_be_divided, pot_split.community)?;
+        let p3d_share = percent_of(to_be_divided, pot_split.p3d)?;
+        let mut f3d_share = percent_of(to_be_divided, pot_split.f3d)?;
+        let mut next_round_share = percent_of(to_be_divided, pot_split.next_round)?;
+
+        //remainder (incl. dust) to winner
         let grand_prize = to_be_divided
             .try_sub(community_share)?
             .try_sub(f3d_share)?
             .try_sub(p3d_share)?
             .try_sub(next_round_share)?;
-        assert!(grand_prize >= to_be_divided.try_mul(48)?.try_floor_div(100)?);
+        assert!(grand_prize >= percent_of(to_be_divided, pot_split.winner)?);
+
+        //distribute f3d share among key holders, any dust goes to next round
+        let f3d_dust = distribute_f3d(&mut round_state, f3d_share)?;
+        f3d_share.try_self_sub(f3d_dust)?;
+        next_round_share.try_self_add(f3d_dust)?;
 
         // --------------------------------------- assign funds to winner
         player_round_state
             .accum_winnings
-            .try_self_add(grand_prize)?;
-        player_round_state.serialize(&mut *winner_state_info.data.borrow_mut())?;
+            .try_self_add(grand_prize.try_add(winner_bounty)?)?;
+        player_round_state.serialize(&mut &mut winner_state_info.data.borrow_mut()[..])?;
 
         // --------------------------------------- update round state
         round_state.ended = true;
@@ -791,13 +1685,191 @@ impl Processor {
         round_state.accum_f3d_share.try_self_add(f3d_share)?;
         round_state.final_prize_share.try_self_add(grand_prize)?;
         round_state.still_in_play = 0;
-        round_state.serialize(&mut *round_state_info.data.borrow_mut())?;
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
 
         verify_round_state(&round_state)?;
 
         Ok(())
     }
 
+    pub fn process_end_and_advance(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let funder_info = next_account_info(account_info_iter)?;
+        let game_state_info = next_account_info(account_info_iter)?;
+        let round_state_info = next_account_info(account_info_iter)?;
+        let pot_info = next_account_info(account_info_iter)?;
+        let next_round_state_info = next_account_info(account_info_iter)?;
+        let next_pot_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        verify_account_count(accounts, 10, 13)?;
+
+        // --------------------------------------- end current round
+        //the round might have been ended separately already, in which case only advance
+        let (game_state, _, _) = deserialize_game_state(game_state_info, program_id)?;
+        let round_state = deserialize_round_state(
+            round_state_info,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        if !round_state.ended {
+            //same accounts as EndRound, with the funder collecting any bounty
+            let mut end_accounts = vec![game_state_info.clone(), round_state_info.clone()];
+            if !round_state.lead_player_pk.is_empty() {
+                let winner_state_info =
+                    account_info_iter.next().ok_or(GameError::MissingAccount)?;
+                end_accounts.push(winner_state_info.clone());
+            }
+            if game_state.end_round_bounty.is_enabled() {
+                let funder_round_state_info =
+                    account_info_iter.next().ok_or(GameError::MissingAccount)?;
+                let funder_state_info =
+                    account_info_iter.next().ok_or(GameError::MissingAccount)?;
+                end_accounts.push(funder_info.clone());
+                end_accounts.push(funder_round_state_info.clone());
+                end_accounts.push(system_program_info.clone());
+                end_accounts.push(funder_state_info.clone());
+            }
+            Self::process_end_round(program_id, &end_accounts)?;
+        }
+
+        // --------------------------------------- start next round
+        let init_accounts = [
+            funder_info.clone(),
+            game_state_info.clone(),
+            next_round_state_info.clone(),
+            next_pot_info.clone(),
+            mint_info.clone(),
+            rent_info.clone(),
+            system_program_info.clone(),
+            token_program_info.clone(),
+            round_state_info.clone(),
+            pot_info.clone(),
+        ];
+        Self::process_initialize_round(program_id, &init_accounts)
+    }
+
+    pub fn process_register_name(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name_params: RegisterNameParams,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let player_info = next_account_info(account_info_iter)?;
+        let game_state_info = next_account_info(account_info_iter)?;
+        let round_state_info = next_account_info(account_info_iter)?;
+        let name_state_info = next_account_info(account_info_iter)?;
+        let id_state_info = next_account_info(account_info_iter)?;
+        let pot_info = next_account_info(account_info_iter)?;
+        let player_token_acc_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let mut expected_owners = [
+            Owner::SystemProgram,
+            Owner::Other(*program_id),
+            Owner::Other(*program_id),
+            Owner::SystemProgram,
+            Owner::SystemProgram,
+            Owner::TokenProgram,
+            Owner::TokenProgram,
+            Owner::NativeLoader,
+            Owner::BPFLoader,
+        ];
+        //change the owner if already registered, so that it's reported as taken below
+        let name_taken = account_exists(name_state_info);
+        if name_taken {
+            expected_owners[3] = Owner::Other(*program_id);
+        }
+        verify_account_ownership(accounts, &expected_owners)?;
+        verify_account_count(accounts, 9, 9)?;
+        verify_is_signer(player_info)?;
+        verify_token_program(token_program_info)?;
+        verify_rent_exempt(&[
+            game_state_info,
+            round_state_info,
+            pot_info,
+            player_token_acc_info,
+        ])?;
+
+        let RegisterNameParams { name } = name_params;
+        let name = normalize_name(&name)?;
+        if name_taken {
+            return Err(GameError::NameTaken.into());
+        }
+
+        let (mut game_state, _, _) = deserialize_game_state(game_state_info, program_id)?;
+        let mut round_state = deserialize_round_state(
+            round_state_info,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        deserialize_pot(
+            pot_info,
+            game_state_info,
+            game_state.round_id,
+            game_state.version,
+            program_id,
+        )?;
+        game_state.name_count.try_self_add(1)?;
+        let id = game_state.name_count;
+        let mut name_state = create_name_state(
+            name_state_info,
+            player_info,
+            system_program_info,
+            &name,
+            game_state.version,
+            program_id,
+        )?;
+        let mut id_state = create_id_state(
+            id_state_info,
+            player_info,
+            system_program_info,
+            id,
+            game_state.version,
+            program_id,
+        )?;
+
+        // --------------------------------------- pay the fee
+        if game_state.name_fee > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: player_token_acc_info.clone(),
+                destination: pot_info.clone(),
+                authority: player_info.clone(),
+                token_program: token_program_info.clone(),
+                amount: game_state.name_fee.try_cast()?,
+                authority_signer_seeds: &[],
+            })?;
+            //counts towards the pot's balance, but not the curve position - key price stays put
+            round_state
+                .accum_sol_pot
+                .try_self_add(game_state.name_fee)?;
+            round_state
+                .accum_community_share
+                .try_self_add(game_state.name_fee)?;
+            round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+            verify_round_state(&round_state)?;
+        }
+
+        // --------------------------------------- update name & id state
+        name_state.player_pk = *player_info.key;
+        name_state.id = id;
+        name_state.name_len = name.len() as u8;
+        name_state.name[..name.len()].copy_from_slice(name.as_bytes());
+        name_state.TYPE = StateType::NameStateTypeV1;
+        name_state.serialize(&mut &mut name_state_info.data.borrow_mut()[..])?;
+        id_state.player_pk = *player_info.key;
+        id_state.id = id;
+        id_state.TYPE = StateType::IdStateTypeV1;
+        id_state.serialize(&mut &mut id_state_info.data.borrow_mut()[..])?;
+        game_state.serialize(&mut &mut game_state_info.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
     pub fn process_community_withdrawal(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -869,23 +1941,84 @@ impl Processor {
 
         // --------------------------------------- update round state
         round_state.withdrawn_com.try_self_add(amount_to_withdraw)?;
-        round_state.serialize(&mut *round_state_info.data.borrow_mut())?;
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
 
         Ok(())
     }
 
     pub fn process_p3d_withdrawal(
-        _program_id: &Pubkey,
-        _accounts: &[AccountInfo],
-        _withdraw_params: WithdrawParams,
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        withdraw_params: WithdrawParams,
     ) -> ProgramResult {
-        unimplemented!(
-            "This basically follows the community withdrawal one for one, \
-                        with the only 2 differences being: \
-                        1) the destination account passed (p3d instead of com),\
-                        2) the round state amounts updated (p3d instead of com)\
-                        \
-                        Since the app is for demo purposes, decided not to duplicate code."
-        )
+        let account_info_iter = &mut accounts.iter();
+        let game_state_info = next_account_info(account_info_iter)?;
+        let round_state_info = next_account_info(account_info_iter)?;
+        let pot_info = next_account_info(account_info_iter)?;
+        let p3d_wallet_info = next_account_info(account_info_iter)?;
+        let p3d_wallet_owner_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let expected_owners = [
+            Owner::Other(*program_id),
+            Owner::Other(*program_id),
+            Owner::TokenProgram,
+            Owner::TokenProgram,
+            Owner::SystemProgram,
+            Owner::BPFLoader,
+        ];
+        verify_account_ownership(accounts, &expected_owners)?;
+        verify_account_count(accounts, 6, 6)?;
+        verify_is_signer(p3d_wallet_owner_info)?;
+        verify_token_program(token_program_info)?;
+        verify_rent_exempt(&[game_state_info, round_state_info, pot_info, p3d_wallet_info])?;
+
+        let WithdrawParams { withdraw_for_round } = withdraw_params;
+
+        let (game_state, game_state_seed, game_state_bump) =
+            deserialize_game_state(game_state_info, program_id)?;
+        let mut round_state = deserialize_round_state(
+            round_state_info,
+            withdraw_for_round,
+            game_state.version,
+            program_id,
+        )?;
+        deserialize_pot(
+            pot_info,
+            game_state_info,
+            withdraw_for_round,
+            game_state.version,
+            program_id,
+        )?;
+        //ensure the right p3d wallet is passed
+        if game_state.p3d_wallet != *p3d_wallet_info.key {
+            return Err(GameError::WrongAccount.into());
+        }
+        //ensure tx comes from p3d wallet's owner
+        let p3d_wallet = Account::unpack(&p3d_wallet_info.data.borrow_mut())?;
+        if p3d_wallet.owner != *p3d_wallet_owner_info.key {
+            return Err(GameError::InvalidOwner.into());
+        }
+
+        // --------------------------------------- transfer tokens
+        let amount_to_withdraw = round_state
+            .accum_p3d_share
+            .try_sub(round_state.withdrawn_p3d)?;
+        if amount_to_withdraw == 0 {
+            return Ok(());
+        }
+        spl_token_transfer(TokenTransferParams {
+            source: pot_info.clone(),
+            destination: p3d_wallet_info.clone(),
+            amount: amount_to_withdraw.try_cast()?,
+            authority: game_state_info.clone(),
+            authority_signer_seeds: &[game_state_seed.as_bytes(), &[game_state_bump]],
+            token_program: token_program_info.clone(),
+        })?;
+
+        // --------------------------------------- update round state
+        round_state.withdrawn_p3d.try_self_add(amount_to_withdraw)?;
+        round_state.serialize(&mut &mut round_state_info.data.borrow_mut()[..])?;
+
+        Ok(())
     }
 }
@@ -1,16 +1,112 @@
 use std::convert::TryInto;
 
-use solana_program::{clock::Clock, program_error::ProgramError, pubkey::Pubkey};
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::AccountInfo, clock::Clock, msg, program_error::ProgramError, pubkey::Pubkey,
+};
 
-use crate::math::common::{TryAdd, TryRem};
+use crate::{
+    error::GameError,
+    math::common::{TryAdd, TryRem},
+    state::{AirdropConfig, OracleResult, ORACLE_RESULT_SIZE},
+};
+
+/// Source of the airdrop lottery rolls, picked per game at init (see RandomnessSource).
+pub trait RandomnessProvider {
+    /// Rolls a number in the [0,1000) range for the player.
+    fn roll(&self, player_pk: &Pubkey) -> Result<u128, ProgramError>;
+}
+
+/// Built-in hash of the player's key, the clock and optionally the latest slot hash, see pseudo_rng.
+pub struct ClockRng<'a> {
+    pub clock: &'a Clock,
+    pub slot_hash: Option<[u8; 32]>,
+}
+
+impl RandomnessProvider for ClockRng<'_> {
+    fn roll(&self, player_pk: &Pubkey) -> Result<u128, ProgramError> {
+        pseudo_rng(player_pk, self.clock, self.slot_hash.as_ref())
+    }
+}
+
+/// Optional accounts the randomness providers read from, passed last to the purchasing instructions.
+#[derive(Clone, Copy, Default)]
+pub struct RandomnessAccounts<'r, 'a> {
+    pub oracle_result: Option<&'r AccountInfo<'a>>,
+    pub slot_hashes: Option<&'r AccountInfo<'a>>,
+}
+
+/// Player's revealed secret mixed with a later slot hash, see commit_reveal_rng.
+pub struct CommitRevealRng {
+    pub secret: [u8; 32],
+    pub slot_hash: [u8; 32],
+}
+
+impl RandomnessProvider for CommitRevealRng {
+    fn roll(&self, _player_pk: &Pubkey) -> Result<u128, ProgramError> {
+        commit_reveal_rng(&self.secret, &self.slot_hash)
+    }
+}
+
+/// Randomness published by a trusted oracle (eg a VRF) into a result account it owns.
+/// Every player rolls on the same published value, mixed with their own key - so each entry is bound
+/// to the one result published right after it was made, see roll_oracle_entry.
+pub struct OracleRng {
+    pub seq: u64,
+    pub randomness: [u8; 32],
+}
+
+impl OracleRng {
+    /// Reads the oracle's result account. (!) Checking it's the pinned one, owned by the game's oracle,
+    /// is left to the caller.
+    pub fn load(
+        result_info: &AccountInfo,
+        airdrop: &AirdropConfig,
+        clock: &Clock,
+    ) -> Result<Self, ProgramError> {
+        let data = result_info.data.borrow();
+        if data.len() < ORACLE_RESULT_SIZE {
+            return Err(GameError::UnpackingFailure.into());
+        }
+        let result = OracleResult::deserialize(&mut &data[..ORACLE_RESULT_SIZE])
+            .map_err(|_| GameError::UnpackingFailure)?;
+        if result.slot.try_add(airdrop.oracle_max_age)? < clock.slot {
+            msg!(
+                "oracle result is from slot {}, now {}",
+                result.slot,
+                clock.slot
+            );
+            return Err(GameError::StaleRandomness.into());
+        }
+        Ok(Self {
+            seq: result.seq,
+            randomness: result.randomness,
+        })
+    }
+}
+
+impl RandomnessProvider for OracleRng {
+    fn roll(&self, player_pk: &Pubkey) -> Result<u128, ProgramError> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.randomness);
+        data.extend_from_slice(&player_pk.to_bytes());
+        hash_to_roll(&data)
+    }
+}
 
 /// Generates a pseudo-random number in the [0,1000) range.
+/// The clock fields alone can be reproduced by any other program within the same slot,
+/// so the most recent slot hash is mixed in too, when available.
 /// (!) NOT A REAL RANDOM NUMBER GENERATOR
 ///     Real rng would come from an off-chain oracle, which currently doesn't exist on Solana.
 ///     This rng is predictable and is purely used for demonstration purposes.
 ///     In fact the original Fomo3D protocol has a security vulnerability due to using an on-chain rng -
 ///     https://www.reddit.com/r/ethereum/comments/916xni/how_to_pwn_fomo3d_a_beginners_guide/
-pub fn pseudo_rng(player_pk: &Pubkey, clock: &Clock) -> Result<u128, ProgramError> {
+pub fn pseudo_rng(
+    player_pk: &Pubkey,
+    clock: &Clock,
+    slot_hash: Option<&[u8; 32]>,
+) -> Result<u128, ProgramError> {
     let mut data = vec![];
 
     let local = player_pk.to_bytes();
@@ -22,8 +118,70 @@ pub fn pseudo_rng(player_pk: &Pubkey, clock: &Clock) -> Result<u128, ProgramErro
 
     data.extend_from_slice(&local);
     data.extend_from_slice(&temporal.to_le_bytes());
+    if let Some(slot_hash) = slot_hash {
+        data.extend_from_slice(slot_hash);
+    }
+
+    hash_to_roll(&data)
+}
+
+/// Generates a random number in the [0,1000) range from the player's revealed secret and the
+/// hash of a slot that came after they committed to it - neither side can pick the outcome alone.
+/// (!) The leader producing that slot can still withhold it, so this is not a proper VRF either.
+pub fn commit_reveal_rng(secret: &[u8; 32], slot_hash: &[u8; 32]) -> Result<u128, ProgramError> {
+    let mut data = vec![];
+    data.extend_from_slice(secret);
+    data.extend_from_slice(slot_hash);
+    hash_to_roll(&data)
+}
+
+// SlotHashes is too large to deserialize on-chain, so it's read in place:
+// u64 entry count, followed by (u64 slot, [u8; 32] hash) entries, most recent first.
+const SLOT_HASH_ENTRY_SIZE: usize = 8 + 32;
+
+fn slot_hash_count(slot_hashes_data: &[u8]) -> Result<usize, ProgramError> {
+    let count = slot_hashes_data
+        .get(..8)
+        .ok_or(GameError::UnpackingFailure)?;
+    Ok(u64::from_le_bytes(count.try_into().unwrap()) as usize)
+}
+
+fn slot_hash_entry(slot_hashes_data: &[u8], i: usize) -> Result<(u64, [u8; 32]), ProgramError> {
+    let entry = slot_hashes_data
+        .get(8 + i * SLOT_HASH_ENTRY_SIZE..8 + (i + 1) * SLOT_HASH_ENTRY_SIZE)
+        .ok_or(GameError::UnpackingFailure)?;
+    let slot = u64::from_le_bytes(entry[..8].try_into().unwrap());
+    Ok((slot, entry[8..].try_into().unwrap()))
+}
 
-    let hash = solana_program::keccak::hash(&data).to_bytes();
+/// Finds the hash of the first slot after the given one in raw SlotHashes sysvar data.
+/// Returns None if no later slot has been recorded yet.
+pub fn slot_hash_after(
+    slot_hashes_data: &[u8],
+    slot: u64,
+) -> Result<Option<[u8; 32]>, ProgramError> {
+    let mut found = None;
+    for i in 0..slot_hash_count(slot_hashes_data)? {
+        let (entry_slot, hash) = slot_hash_entry(slot_hashes_data, i)?;
+        if entry_slot <= slot {
+            break;
+        }
+        found = Some(hash);
+    }
+    Ok(found)
+}
+
+/// Returns the most recent hash in raw SlotHashes sysvar data.
+pub fn latest_slot_hash(slot_hashes_data: &[u8]) -> Result<[u8; 32], ProgramError> {
+    if slot_hash_count(slot_hashes_data)? == 0 {
+        return Err(GameError::UnpackingFailure.into());
+    }
+    let (_, hash) = slot_hash_entry(slot_hashes_data, 0)?;
+    Ok(hash)
+}
+
+fn hash_to_roll(data: &[u8]) -> Result<u128, ProgramError> {
+    let hash = solana_program::keccak::hash(data).to_bytes();
     let short_hash = &hash[..16];
     let hash_int = u128::from_le_bytes(short_hash.try_into().unwrap());
     hash_int.try_rem(1000)
@@ -46,9 +204,46 @@ mod tests {
         )
         .start()
         .await;
-        let clock = banks_client.get_clock().await.unwrap();
+        let clock = banks_client.get_sysvar::<Clock>().await.unwrap();
         let player_pk = Pubkey::new_unique();
-        let result = pseudo_rng(&player_pk, &clock).unwrap();
+        let result = pseudo_rng(&player_pk, &clock, None).unwrap();
         assert!(0 <= result && result < 1000);
+        let with_slot_hash = pseudo_rng(&player_pk, &clock, Some(&[1; 32])).unwrap();
+        assert!(with_slot_hash < 1000);
+    }
+
+    #[test]
+    fn test_oracle_rng() {
+        let oracle_rng = OracleRng {
+            seq: 1,
+            randomness: [9; 32],
+        };
+        let player_pk = Pubkey::new_unique();
+        let roll = oracle_rng.roll(&player_pk).unwrap();
+        assert!(roll < 1000);
+        //same published value, same player => same roll
+        assert_eq!(oracle_rng.roll(&player_pk).unwrap(), roll);
+        let next_rng = OracleRng {
+            seq: 2,
+            randomness: [10; 32],
+        };
+        assert_ne!(next_rng.roll(&player_pk).unwrap(), roll);
+    }
+
+    #[test]
+    fn test_slot_hashes() {
+        let mut data = vec![];
+        data.extend_from_slice(&3u64.to_le_bytes());
+        for (slot, byte) in [(30u64, 3u8), (20, 2), (10, 1)] {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[byte; 32]);
+        }
+        assert_eq!(slot_hash_after(&data, 5).unwrap(), Some([1; 32]));
+        assert_eq!(slot_hash_after(&data, 10).unwrap(), Some([2; 32]));
+        assert_eq!(slot_hash_after(&data, 25).unwrap(), Some([3; 32]));
+        assert_eq!(slot_hash_after(&data, 30).unwrap(), None);
+        assert!(slot_hash_after(&data[..50], 5).is_err());
+        assert_eq!(latest_slot_hash(&data).unwrap(), [3; 32]);
+        assert!(latest_slot_hash(&0u64.to_le_bytes()).is_err());
     }
 }
@@ -1,15 +1,35 @@
+use borsh::BorshDeserialize;
 use solana_program::{
-    account_info::AccountInfo, entrypoint::ProgramResult, msg, pubkey::Pubkey, rent::Rent,
-    sysvar::Sysvar,
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::{
+        instructions::{self, load_current_index_checked, load_instruction_at_checked},
+        slot_hashes, Sysvar,
+    },
 };
 
 use crate::{
     error::GameError,
+    instruction::GameInstruction,
     math::common::TryAdd,
-    processor::util::load_pk,
+    processor::{
+        pda::AffiliateAccounts,
+        rng::RandomnessAccounts,
+        util::{account_exists, load_pk},
+    },
     state::{
-        GameState, PlayerRoundState, RoundState,
-        StateType::{GameStateTypeV1, PlayerRoundStateTypeV1, RoundStateTypeV1},
+        AirdropConfig, GameState, IdState, NameState, PlayerRoundState, PlayerState,
+        RandomnessSource, RoundState,
+        StateType::{
+            GameStateTypeV1, IdStateTypeV1, NameStateTypeV1, PlayerRoundStateTypeV1,
+            PlayerStateTypeV1, RoundStateTypeV1,
+        },
+        TeamConfig, MAX_AIRDROP_TIERS, MAX_REVEAL_WINDOW,
     },
 };
 
@@ -30,11 +50,108 @@ pub fn verify_round_state(round_state: &RoundState) -> ProgramResult {
         .try_add(round_state.accum_p3d_share)?
         .try_add(round_state.accum_f3d_share)?
         .try_add(round_state.still_in_play)?
-        .try_add(round_state.final_prize_share)?;
+        .try_add(round_state.final_prize_share)?
+        .try_add(round_state.bounty_paid)?
+        .try_add(round_state.airdrop_won)?;
     assert_eq!(actual_money_in_pot, supposed_money_in_pot);
     Ok(())
 }
 
+/// Checks that every team's fee and pot splits add up to exactly 100%.
+pub fn verify_splits(teams: &[TeamConfig]) -> ProgramResult {
+    for team in teams {
+        let f = &team.fee_split;
+        let total = [
+            f.community,
+            f.airdrop,
+            f.next_round,
+            f.affiliate,
+            f.f3d,
+            f.p3d,
+            f.pot,
+        ]
+        .iter()
+        .map(|&x| x as u16)
+        .sum::<u16>();
+        if total != 100 {
+            msg!("fee split adds up to {}%", total);
+            return Err(GameError::InvalidSplit.into());
+        }
+        let p = &team.pot_split;
+        let total = [p.winner, p.community, p.next_round, p.f3d, p.p3d]
+            .iter()
+            .map(|&x| x as u16)
+            .sum::<u16>();
+        if total != 100 {
+            msg!("pot split adds up to {}%", total);
+            return Err(GameError::InvalidSplit.into());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that airdrop tiers are within bounds and ordered by min stake,
+/// and that commit-reveal entries can still be verified until the end of the reveal window.
+pub fn verify_airdrop_config(airdrop: &AirdropConfig) -> ProgramResult {
+    if airdrop.tier_count as usize > MAX_AIRDROP_TIERS || airdrop.reveal_window > MAX_REVEAL_WINDOW
+    {
+        return Err(GameError::InvalidAirdropConfig.into());
+    }
+    let tiers = &airdrop.tiers[..airdrop.tier_count as usize];
+    if tiers.iter().any(|tier| tier.payout_percent > 100)
+        || tiers.windows(2).any(|w| w[0].min_stake >= w[1].min_stake)
+    {
+        return Err(GameError::InvalidAirdropConfig.into());
+    }
+    Ok(())
+}
+
+/// Programs a transaction may still call after a top-level purchase. Any other could look at the
+/// outcome (eg whether the airdrop was won) and fail the transaction to undo it.
+const ALLOWED_AFTER_PURCHASE: [Pubkey; 1] =
+    [pubkey!("ComputeBudget111111111111111111111111111111")];
+
+/// Rejects purchases made through CPI, or bundled with another purchase into the same round.
+/// Otherwise a contract can buy keys, check the airdrop outcome and revert on a loss -
+/// which is how the original Fomo3D's airdrop got drained.
+pub fn verify_top_level_purchase(
+    instructions_info: &AccountInfo,
+    program_id: &Pubkey,
+    round_state_pk: &Pubkey,
+) -> ProgramResult {
+    //when called through CPI, the current top-level instruction belongs to the calling program
+    let current_index = load_current_index_checked(instructions_info)?;
+    let current = load_instruction_at_checked(current_index as usize, instructions_info)?;
+    if current.program_id != *program_id {
+        return Err(GameError::NotTopLevel.into());
+    }
+
+    let mut purchases = 0;
+    let mut index = 0;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions_info) {
+        let is_purchase = ix.program_id == *program_id
+            && matches!(
+                GameInstruction::try_from_slice(&ix.data),
+                Ok(GameInstruction::PurchaseKeys(_)) | Ok(GameInstruction::ReloadKeys(_))
+            );
+        //round state is at the same position for both instructions
+        if is_purchase && ix.accounts.get(2).map(|acc| acc.pubkey) == Some(*round_state_pk) {
+            purchases += 1;
+        }
+        if index > current_index as usize
+            && ix.program_id != *program_id
+            && !ALLOWED_AFTER_PURCHASE.contains(&ix.program_id)
+        {
+            return Err(GameError::UnexpectedInstruction.into());
+        }
+        index += 1;
+    }
+    if purchases > 1 {
+        return Err(GameError::MultiplePurchases.into());
+    }
+    Ok(())
+}
+
 pub trait VerifyType {
     fn verify_type(&self) -> ProgramResult;
 }
@@ -62,6 +179,30 @@ impl VerifyType for PlayerRoundState {
         Ok(())
     }
 }
+impl VerifyType for PlayerState {
+    fn verify_type(&self) -> ProgramResult {
+        if self.TYPE != PlayerStateTypeV1 {
+            return Err(GameError::InvalidStateType.into());
+        }
+        Ok(())
+    }
+}
+impl VerifyType for NameState {
+    fn verify_type(&self) -> ProgramResult {
+        if self.TYPE != NameStateTypeV1 {
+            return Err(GameError::InvalidStateType.into());
+        }
+        Ok(())
+    }
+}
+impl VerifyType for IdState {
+    fn verify_type(&self) -> ProgramResult {
+        if self.TYPE != IdStateTypeV1 {
+            return Err(GameError::InvalidStateType.into());
+        }
+        Ok(())
+    }
+}
 
 // --------------------------------------- accounts
 
@@ -130,6 +271,121 @@ pub fn verify_rent_exempt(accounts: &[&AccountInfo]) -> ProgramResult {
     Ok(())
 }
 
+/// Affiliates are passed either as their own account or as a name / id they registered with the game.
+pub fn affiliate_code_owner(affiliate_code_info: &AccountInfo, program_id: &Pubkey) -> Owner {
+    if affiliate_code_info.owner == program_id {
+        Owner::Other(*program_id)
+    } else {
+        Owner::SystemProgram
+    }
+}
+
+/// Pdas the instruction creates if missing are still owned by the system program until then.
+pub fn pda_owner(pda_info: &AccountInfo, program_id: &Pubkey) -> Owner {
+    if account_exists(pda_info) {
+        Owner::Other(*program_id)
+    } else {
+        Owner::SystemProgram
+    }
+}
+
+/// Optional accounts shared by PurchaseKeys and ReloadKeys, see the former for their order.
+pub struct PurchaseAccounts<'r, 'a> {
+    pub affiliate: Option<AffiliateAccounts<'r, 'a>>,
+    pub randomness: RandomnessAccounts<'r, 'a>,
+    pub instructions: Option<&'r AccountInfo<'a>>,
+}
+
+/// Takes the optional affiliate, randomness and Instructions accounts off the iterator + pushes
+/// their expected owners. `extra_account_count` accounts after them are left to the caller.
+pub fn parse_purchase_accounts<'r, 'a, I>(
+    account_info_iter: &mut I,
+    accounts: &[AccountInfo<'a>],
+    game_state: &GameState,
+    expected_owners: &mut Vec<Owner>,
+    extra_account_count: usize,
+    program_id: &Pubkey,
+) -> Result<PurchaseAccounts<'r, 'a>, ProgramError>
+where
+    I: Iterator<Item = &'r AccountInfo<'a>>,
+    'a: 'r,
+{
+    let uses_oracle = game_state.randomness == RandomnessSource::Oracle;
+    let has_slot_hashes = accounts.iter().any(|acc| *acc.key == slot_hashes::id());
+    let has_instructions = accounts.iter().any(|acc| *acc.key == instructions::id());
+    if game_state.strong_rng && !has_slot_hashes
+        || game_state.top_level_purchases && !has_instructions
+    {
+        return Err(GameError::MissingAccount.into());
+    }
+    let trailing_account_count = uses_oracle as usize
+        + has_slot_hashes as usize
+        + has_instructions as usize
+        + extra_account_count;
+
+    //whatever isn't accounted for belongs to the affiliate
+    let mut affiliate = None;
+    let affiliate_account_count = accounts
+        .len()
+        .saturating_sub(expected_owners.len() + trailing_account_count);
+    if affiliate_account_count > 0 {
+        if affiliate_account_count < 3 {
+            return Err(GameError::MissingAccount.into());
+        }
+        let round_state = next_account_info(account_info_iter)?;
+        let code = next_account_info(account_info_iter)?;
+        let player_state = next_account_info(account_info_iter)?;
+        expected_owners.push(pda_owner(round_state, program_id));
+        expected_owners.push(affiliate_code_owner(code, program_id));
+        expected_owners.push(pda_owner(player_state, program_id));
+        //the next two are the affiliate's own affiliate, for games paying a second tier
+        let mut second_tier = None;
+        if affiliate_account_count > 3 {
+            let round_state = next_account_info(account_info_iter)?;
+            let player_state = next_account_info(account_info_iter)?;
+            expected_owners.push(pda_owner(round_state, program_id));
+            expected_owners.push(pda_owner(player_state, program_id));
+            second_tier = Some((round_state, player_state));
+        }
+        affiliate = Some(AffiliateAccounts {
+            round_state,
+            code,
+            player_state,
+            second_tier,
+        });
+    }
+    let mut randomness = RandomnessAccounts::default();
+    if uses_oracle {
+        let info = next_account_info(account_info_iter)?;
+        if *info.key != game_state.airdrop.oracle_result {
+            return Err(GameError::WrongAccount.into());
+        }
+        randomness.oracle_result = Some(info);
+        expected_owners.push(Owner::Other(game_state.airdrop.oracle));
+    }
+    if has_slot_hashes {
+        let info = next_account_info(account_info_iter)?;
+        if *info.key != slot_hashes::id() {
+            return Err(GameError::WrongAccount.into());
+        }
+        expected_owners.push(Owner::Sysvar);
+        //only mixed in when the game requires it - otherwise the buyer could pick between two rolls
+        if game_state.strong_rng {
+            randomness.slot_hashes = Some(info);
+        }
+    }
+    let mut instructions = None;
+    if has_instructions {
+        instructions = Some(next_account_info(account_info_iter)?);
+        expected_owners.push(Owner::Sysvar);
+    }
+    Ok(PurchaseAccounts {
+        affiliate,
+        randomness,
+        instructions,
+    })
+}
+
 // --------------------------------------- CPI
 
 pub fn verify_token_program(token_program: &AccountInfo) -> ProgramResult {
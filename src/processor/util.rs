@@ -1,42 +1,224 @@
 use std::str::FromStr;
 
 use solana_program::{
-    account_info::AccountInfo, clock::Clock, msg, program_error::ProgramError, pubkey::Pubkey,
-    sysvar::Sysvar,
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, msg,
+    program_error::ProgramError, pubkey::Pubkey, sysvar::Sysvar,
 };
 
 use crate::{
     error::GameError,
-    math::common::{TryDiv, TryMul},
-    processor::rng::pseudo_rng,
-    state::{GameState, RoundState},
+    math::common::{TryAdd, TryCast, TryDiv, TryMul, TrySub},
+    processor::rng::{OracleRng, RandomnessProvider},
+    state::{
+        AirdropConfig, EndRoundBounty, GameState, PlayerRoundState, PlayerState, RoundState,
+        TimingMode, UnixTimestamp, MAX_END_ROUND_BOUNTY_PERCENT, MAX_NAME_LEN,
+        MAX_UNCLAIMED_ROUNDS, PROFIT_PER_KEY_PRECISION,
+    },
 };
 
-/// The original math for this is unnecessary convoluted and we decided to ignore it.
-/// Ultimately this comes down to a simple equation: (player's keys / total keys) * total f3d earnings.
-/// That's the approach taken below. For anyone interested in original math follow these links:
-/// https://gist.github.com/ilmoi/4daad0d6e9730cc6af833c065a95b717#file-fomo-sol-L1533
-/// https://gist.github.com/ilmoi/4daad0d6e9730cc6af833c065a95b717#file-fomo-sol-L1125
+/// Calculates a percentage share of the amount, rounding down.
+pub fn percent_of(amount: u128, percent: u8) -> Result<u128, ProgramError> {
+    amount.try_mul(percent as u128)?.try_floor_div(100)
+}
+
+/// Bounty = percent of what's still in play + fixed amount, capped at MAX_END_ROUND_BOUNTY_PERCENT
+/// of what's still in play - the fixed amount can't eat into the winner's prize beyond that.
+pub fn calc_end_round_bounty(
+    bounty: &EndRoundBounty,
+    still_in_play: u128,
+) -> Result<u128, ProgramError> {
+    let max_bounty = percent_of(still_in_play, MAX_END_ROUND_BOUNTY_PERCENT)?;
+    let bounty = percent_of(still_in_play, bounty.percent)?.try_add(bounty.amount)?;
+    Ok(bounty.min(max_bounty))
+}
+
+/// Spreads the f3d share evenly across all keys in the round by bumping the round's profit per key.
+/// Returns the dust lost to rounding, which the caller is expected to put back somewhere.
+pub fn distribute_f3d(round_state: &mut RoundState, f3d_share: u128) -> Result<u128, ProgramError> {
+    if round_state.accum_keys == 0 {
+        return Ok(f3d_share);
+    }
+    let profit_per_key = f3d_share
+        .try_mul(PROFIT_PER_KEY_PRECISION)?
+        .try_floor_div(round_state.accum_keys)?;
+    round_state.profit_per_key.try_self_add(profit_per_key)?;
+    let distributed = profit_per_key
+        .try_mul(round_state.accum_keys)?
+        .try_floor_div(PROFIT_PER_KEY_PRECISION)?;
+    f3d_share.try_sub(distributed)
+}
+
+/// Same as updateMasks() in the original.
+/// Expects both round and player keys to already include the newly purchased keys.
+/// The buyer's mask is bumped so that they only earn from this purchase onwards.
+/// Returns the dust lost to rounding.
+pub fn update_masks(
+    round_state: &mut RoundState,
+    player_round_state: &mut PlayerRoundState,
+    f3d_share: u128,
+    new_keys: u128,
+) -> Result<u128, ProgramError> {
+    let profit_per_key_before = round_state.profit_per_key;
+    let dust = distribute_f3d(round_state, f3d_share)?;
+    //what the new keys earn from this very purchase
+    let player_earnings = round_state
+        .profit_per_key
+        .try_sub(profit_per_key_before)?
+        .try_mul(new_keys)?
+        .try_floor_div(PROFIT_PER_KEY_PRECISION)?;
+    //mask out everything the new keys would be entitled to up to now, bar the above
+    let mask = round_state
+        .profit_per_key
+        .try_mul(new_keys)?
+        .try_floor_div(PROFIT_PER_KEY_PRECISION)?
+        .try_sub(player_earnings)?;
+    player_round_state.f3d_mask.try_self_add(mask)?;
+    Ok(dust)
+}
+
+/// Same as calcUnMaskedEarnings() in the original - f3d earnings not yet withdrawn by the player.
 pub fn calculate_player_f3d_share(
-    player_keys: u128,
-    total_keys: u128,
-    accum_f3d: u128,
+    player_round_state: &PlayerRoundState,
+    round_state: &RoundState,
 ) -> Result<u128, ProgramError> {
-    //in theory, there might be unaccounted dust left here.
-    //eg player1 keys = 333, player2 keys =  total keys = 1000, f3t pot = 100
-    //then player1 will get 33, player2 will get 66, and 1 will be left as dust
-    //in practice, however, to account for it would have to coordinate all withdrawals by all players
-    //which of course isn't possible. So it will just be left in the protocol
-    player_keys.try_mul(accum_f3d)?.try_floor_div(total_keys)
+    round_state
+        .profit_per_key
+        .try_mul(player_round_state.accum_keys)?
+        .try_floor_div(PROFIT_PER_KEY_PRECISION)?
+        .try_sub(player_round_state.f3d_mask)
+}
+
+/// What a player can currently take out of a round, broken down by source.
+pub struct PlayerClaimable {
+    pub winnings: u128,
+    pub aff: u128,
+    pub f3d: u128,
+}
+
+impl PlayerClaimable {
+    pub fn total(&self) -> Result<u128, ProgramError> {
+        self.winnings.try_add(self.aff)?.try_add(self.f3d)
+    }
+}
+
+/// Winnings, affiliate rewards and f3d earnings not yet withdrawn (or reloaded) by the player.
+pub fn calculate_player_claimable(
+    player_round_state: &PlayerRoundState,
+    round_state: &RoundState,
+) -> Result<PlayerClaimable, ProgramError> {
+    Ok(PlayerClaimable {
+        winnings: player_round_state
+            .accum_winnings
+            .try_sub(player_round_state.withdrawn_winnings)?,
+        aff: player_round_state
+            .accum_aff
+            .try_sub(player_round_state.withdrawn_aff)?,
+        f3d: calculate_player_f3d_share(player_round_state, round_state)?,
+    })
 }
 
-pub fn airdrop_winner(
+/// Marks `amount` out of the player's claimable balance as withdrawn.
+/// Winnings are used up first, then affiliate rewards, then f3d earnings.
+pub fn record_player_withdrawal(
+    player_round_state: &mut PlayerRoundState,
+    claimable: &PlayerClaimable,
+    amount: u128,
+) -> Result<(), ProgramError> {
+    if amount > claimable.total()? {
+        return Err(GameError::InsufficientFunds.into());
+    }
+    let from_winnings = amount.min(claimable.winnings);
+    let from_aff = amount.try_sub(from_winnings)?.min(claimable.aff);
+    let from_f3d = amount.try_sub(from_winnings)?.try_sub(from_aff)?;
+    player_round_state
+        .withdrawn_winnings
+        .try_self_add(from_winnings)?;
+    player_round_state.withdrawn_aff.try_self_add(from_aff)?;
+    player_round_state.withdrawn_f3d.try_self_add(from_f3d)?;
+    player_round_state.f3d_mask.try_self_add(from_f3d)?;
+    Ok(())
+}
+
+/// The lottery is won if the roll, in the [0,1000) range, comes in under the tracker.
+/// Rolling is left to the caller, so that the randomness source can be swapped out (eg in tests).
+pub fn airdrop_winner<F>(roll: F, airdrop_tracker: u64) -> Result<bool, ProgramError>
+where
+    F: FnOnce() -> Result<u128, ProgramError>,
+{
+    let lottery_ticket = roll()?;
+    Ok(lottery_ticket < airdrop_tracker as u128)
+}
+
+/// Prize is a share of the airdrop pot, as per the highest tier whose min stake the purchase is above.
+pub fn airdrop_prize(
+    airdrop: &AirdropConfig,
+    stake: u128,
+    airdrop_pot: u128,
+) -> Result<u128, ProgramError> {
+    let tier = airdrop.tiers[..airdrop.tier_count as usize]
+        .iter()
+        .rev()
+        .find(|tier| stake > tier.min_stake);
+    match tier {
+        Some(tier) => percent_of(airdrop_pot, tier.payout_percent),
+        None => Ok(0),
+    }
+}
+
+/// Credits the player with their share of the airdrop pot and resets the lottery.
+pub fn settle_airdrop_win(
+    airdrop: &AirdropConfig,
+    round_state: &mut RoundState,
+    player_round_state: &mut PlayerRoundState,
+    stake: u128,
+) -> ProgramResult {
+    let prize = airdrop_prize(airdrop, stake, round_state.accum_airdrop_share)?;
+    round_state.accum_airdrop_share.try_self_sub(prize)?;
+    round_state.airdrop_won.try_self_add(prize)?;
+    player_round_state.accum_winnings.try_self_add(prize)?;
+    round_state.airdrop_tracker = 0;
+    Ok(())
+}
+
+/// Rolls the player's pending oracle entry on the result published right after it was made.
+/// Returns true while that result isn't out yet. Any later one forfeits the entry - otherwise the player
+/// could hold off settling until a result they've already seen wins.
+/// Entries past the reveal window are forfeited. Returns whether the entry is still pending.
+pub fn roll_oracle_entry(
+    oracle_rng: &OracleRng,
+    airdrop: &AirdropConfig,
+    round_state: &mut RoundState,
+    player_round_state: &mut PlayerRoundState,
     player_pk: &Pubkey,
     clock: &Clock,
-    airdrop_tracker: u64,
 ) -> Result<bool, ProgramError> {
-    let lottery_ticket = pseudo_rng(player_pk, clock)?;
-    Ok(lottery_ticket < airdrop_tracker as u128)
+    if reveal_window_expired(player_round_state, airdrop, clock)? {
+        msg!("reveal window has expired, airdrop entry forfeited");
+    } else if oracle_rng.seq <= player_round_state.airdrop_oracle_seq {
+        return Ok(true);
+    } else if oracle_rng.seq > player_round_state.airdrop_oracle_seq.try_add(1)? {
+        msg!("oracle has published past the entry's result, airdrop entry forfeited");
+    } else if airdrop_winner(
+        || oracle_rng.roll(player_pk),
+        player_round_state.airdrop_odds,
+    )? {
+        let stake = player_round_state.airdrop_stake;
+        settle_airdrop_win(airdrop, round_state, player_round_state, stake)?;
+    }
+    player_round_state.clear_pending_airdrop();
+    Ok(false)
+}
+
+/// Commit-reveal & oracle entries not settled within the game's reveal window are forfeited.
+pub fn reveal_window_expired(
+    player_round_state: &PlayerRoundState,
+    airdrop: &AirdropConfig,
+    clock: &Clock,
+) -> Result<bool, ProgramError> {
+    let last_slot = player_round_state
+        .airdrop_commit_slot
+        .try_add(airdrop.reveal_window)?;
+    Ok(clock.slot > last_slot)
 }
 
 pub fn account_exists(acc: &AccountInfo) -> bool {
@@ -61,23 +243,242 @@ impl Empty for Pubkey {
     }
 }
 
-pub fn time_is_out(round_state: &RoundState) -> Result<bool, ProgramError> {
+/// Current time in the game's timing unit - unix timestamp or slot.
+pub fn current_time(game_state: &GameState) -> Result<UnixTimestamp, ProgramError> {
     let clock = Clock::get()?;
-    msg!(
-        "round time left (s): {}",
-        round_state.end_time - clock.unix_timestamp
-    );
-    Ok(round_state.end_time < clock.unix_timestamp)
+    match game_state.timing_mode {
+        TimingMode::Timestamp => Ok(clock.unix_timestamp),
+        TimingMode::Slot => clock.slot.try_cast(),
+    }
+}
+
+pub fn time_is_out(game_state: &GameState, round_state: &RoundState) -> Result<bool, ProgramError> {
+    let now = current_time(game_state)?;
+    let unit = game_state.timing_mode.unit();
+    let time_left = round_state.end_time - now;
+    msg!("round time left ({}): {}", unit, time_left);
+    let anti_snipe = &game_state.anti_snipe;
+    if anti_snipe.window > 0 && (0..=anti_snipe.window).contains(&time_left) {
+        msg!(
+            "anti-snipe window open: purchases extend the round to at least {}{} left",
+            anti_snipe.min_time_left,
+            unit
+        );
+    }
+    Ok(round_state.end_time < now)
+}
+
+/// New end time = minimum of:
+/// - current end time + number of keys purchased * time per key
+/// - max time (24h in the original) from now
+pub fn calc_new_end_time(
+    new_keys: u128,
+    round_state: &RoundState,
+    game_state: &GameState,
+    now: UnixTimestamp,
+) -> Result<UnixTimestamp, ProgramError> {
+    //capping the delay first keeps it within i64 - the round never ends before now, so it doesn't change the result
+    let delay = new_keys
+        .try_mul(game_state.round_inc_time_per_key as u128)?
+        .min(game_state.round_max_time as u128) as i64;
+    let new_end_time = round_state.end_time.try_add(delay)?;
+    let ceiling = now.try_add(game_state.round_max_time)?;
+    Ok(new_end_time.min(ceiling))
+}
+
+/// Same rules as nameFilter() in the original: 1 to MAX_NAME_LEN characters of a-z, 0-9 and single
+/// spaces, no leading / trailing spaces, no "0x" prefix and at least one letter.
+/// Uppercase letters are lowercased, so that names differing only in case are the same name.
+pub fn normalize_name(name: &str) -> Result<String, ProgramError> {
+    let name = name.to_ascii_lowercase();
+    let bytes = name.as_bytes();
+    let valid = !bytes.is_empty()
+        && bytes.len() <= MAX_NAME_LEN
+        && bytes[0] != b' '
+        && bytes[bytes.len() - 1] != b' '
+        && !name.contains("  ")
+        && !name.starts_with("0x")
+        && bytes
+            .iter()
+            .all(|&c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b' ')
+        && bytes.iter().any(|c| c.is_ascii_lowercase());
+    if !valid {
+        return Err(GameError::InvalidName.into());
+    }
+    Ok(name)
 }
 
-/// New added delay = minimum of:
-/// - number of keys purchased * time per key
-/// - 24h from now
-pub fn calc_new_delay(new_keys: u128, game_state: &GameState) -> Result<u128, ProgramError> {
-    let delay_based_on_keys = new_keys.try_mul(game_state.round_inc_time_per_key as u128)?;
-    Ok(delay_based_on_keys.min(game_state.round_max_time as u128))
+/// Updates the player's profile with keys bought in the round, by a purchase or a reload.
+pub fn record_player_purchase(
+    player_state: &mut PlayerState,
+    player_round_state: &PlayerRoundState,
+    round_id: u64,
+    keys: u128,
+    sol_added: u128,
+    team: u8,
+) -> ProgramResult {
+    player_state.lifetime_keys.try_self_add(keys)?;
+    player_state.lifetime_spend.try_self_add(sol_added)?;
+    player_state.preferred_team = team;
+    if player_round_state.has_affiliate_listed() {
+        player_state.default_affiliate_pk = player_round_state.last_affiliate_pk;
+    }
+    track_unclaimed_round(player_state, round_id);
+    Ok(())
+}
+
+/// Adds the round to the player's rounds with unclaimed balances, unless it's already listed.
+/// The list is best-effort: once full, the oldest round is dropped to make room.
+pub fn track_unclaimed_round(player_state: &mut PlayerState, round_id: u64) {
+    if player_state.unclaimed_rounds().contains(&round_id) {
+        return;
+    }
+    if player_state.unclaimed_round_count as usize == MAX_UNCLAIMED_ROUNDS {
+        let oldest = player_state.unclaimed_rounds[0];
+        untrack_unclaimed_round(player_state, oldest);
+    }
+    let count = player_state.unclaimed_round_count as usize;
+    player_state.unclaimed_rounds[count] = round_id;
+    player_state.unclaimed_round_count += 1;
+}
+
+/// Drops the round from the player's rounds with unclaimed balances, keeping the rest in order.
+pub fn untrack_unclaimed_round(player_state: &mut PlayerState, round_id: u64) {
+    let count = player_state.unclaimed_round_count as usize;
+    if let Some(i) = player_state
+        .unclaimed_rounds()
+        .iter()
+        .position(|&r| r == round_id)
+    {
+        player_state.unclaimed_rounds.copy_within(i + 1..count, i);
+        player_state.unclaimed_rounds[count - 1] = 0;
+        player_state.unclaimed_round_count -= 1;
+    }
 }
 
 pub fn load_pk(addr: &str) -> Result<Pubkey, ProgramError> {
     Pubkey::from_str(addr).map_err(|_| GameError::WrongAccount.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{AirdropTier, MAX_AIRDROP_TIERS, PLAYER_STATE_SIZE};
+    use borsh::BorshDeserialize;
+
+    fn roll(n: u128) -> impl FnOnce() -> Result<u128, ProgramError> {
+        move || Ok(n)
+    }
+
+    fn airdrop_config() -> AirdropConfig {
+        let mut tiers = [AirdropTier::default(); MAX_AIRDROP_TIERS];
+        tiers[0] = AirdropTier {
+            min_stake: 100,
+            payout_percent: 25,
+        };
+        tiers[1] = AirdropTier {
+            min_stake: 1_000,
+            payout_percent: 50,
+        };
+        tiers[2] = AirdropTier {
+            min_stake: 10_000,
+            payout_percent: 75,
+        };
+        AirdropConfig {
+            min_stake: 100,
+            odds_step: 1,
+            reveal_window: 0,
+            oracle: Pubkey::default(),
+            oracle_result: Pubkey::default(),
+            oracle_max_age: 0,
+            tier_count: 3,
+            tiers,
+        }
+    }
+
+    #[test]
+    fn test_airdrop_winner() {
+        //tracker at 0 never wins
+        assert!(!airdrop_winner(roll(0), 0).unwrap());
+        //wins when roll is under the tracker
+        assert!(airdrop_winner(roll(4), 5).unwrap());
+        assert!(!airdrop_winner(roll(5), 5).unwrap());
+        assert!(!airdrop_winner(roll(999), 998).unwrap());
+        //tracker at 1000+ always wins
+        assert!(airdrop_winner(roll(999), 1000).unwrap());
+        //errors from the rng are passed on
+        assert!(airdrop_winner(|| Err(GameError::CalculationFailure.into()), 5).is_err());
+    }
+
+    #[test]
+    fn test_airdrop_prize() {
+        let config = airdrop_config();
+        assert_eq!(airdrop_prize(&config, 100, 1_000).unwrap(), 0);
+        assert_eq!(airdrop_prize(&config, 101, 1_000).unwrap(), 250);
+        assert_eq!(airdrop_prize(&config, 1_000, 1_000).unwrap(), 250);
+        assert_eq!(airdrop_prize(&config, 1_001, 1_000).unwrap(), 500);
+        assert_eq!(airdrop_prize(&config, 10_001, 1_000).unwrap(), 750);
+        assert_eq!(airdrop_prize(&config, u128::MAX, 1_000).unwrap(), 750);
+        //tiers past tier_count are ignored
+        let mut config = config;
+        config.tier_count = 1;
+        assert_eq!(airdrop_prize(&config, 10_001, 1_000).unwrap(), 250);
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Satoshi").unwrap(), "satoshi");
+        assert_eq!(normalize_name("justo 2").unwrap(), "justo 2");
+        assert_eq!(normalize_name("a").unwrap(), "a");
+        let longest = "a".repeat(MAX_NAME_LEN);
+        assert_eq!(normalize_name(&longest).unwrap(), longest);
+
+        let invalid = [
+            "",
+            " satoshi",
+            "satoshi ",
+            "sat  oshi",
+            "0xsatoshi",
+            "0X1234",
+            "12345",
+            "sat-oshi",
+            "satoshì",
+        ];
+        for name in invalid {
+            assert!(normalize_name(name).is_err(), "{}", name);
+        }
+        assert!(normalize_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_unclaimed_rounds() {
+        let mut player_state = PlayerState::try_from_slice(&[0; PLAYER_STATE_SIZE]).unwrap();
+        track_unclaimed_round(&mut player_state, 1);
+        track_unclaimed_round(&mut player_state, 2);
+        track_unclaimed_round(&mut player_state, 1);
+        track_unclaimed_round(&mut player_state, 3);
+        assert_eq!(player_state.unclaimed_rounds(), &[1, 2, 3]);
+
+        untrack_unclaimed_round(&mut player_state, 2);
+        assert_eq!(player_state.unclaimed_rounds(), &[1, 3]);
+        untrack_unclaimed_round(&mut player_state, 2);
+        untrack_unclaimed_round(&mut player_state, 3);
+        assert_eq!(player_state.unclaimed_rounds(), &[1]);
+        assert_eq!(player_state.unclaimed_rounds[1], 0);
+
+        for round_id in 2..=MAX_UNCLAIMED_ROUNDS as u64 {
+            track_unclaimed_round(&mut player_state, round_id);
+        }
+        //already listed rounds don't move
+        track_unclaimed_round(&mut player_state, 1);
+        assert_eq!(player_state.unclaimed_rounds()[0], 1);
+        //a full list drops its oldest round
+        track_unclaimed_round(&mut player_state, 100);
+        assert_eq!(player_state.unclaimed_rounds().len(), MAX_UNCLAIMED_ROUNDS);
+        assert_eq!(player_state.unclaimed_rounds()[0], 2);
+        assert_eq!(
+            player_state.unclaimed_rounds()[MAX_UNCLAIMED_ROUNDS - 1],
+            100
+        );
+    }
+}
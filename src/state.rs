@@ -10,11 +10,33 @@ pub enum StateType {
     GameStateTypeV1,
     RoundStateTypeV1,
     PlayerRoundStateTypeV1,
+    NameStateTypeV1,
+    IdStateTypeV1,
+    PlayerStateTypeV1,
 }
 
 // --------------------------------------- game state
 
-pub const GAME_STATE_SIZE: usize = 1 + (8 * 5) + (32 * 4);
+pub const GAME_STATE_SIZE: usize = 1
+    + (8 * 5)
+    + ANTI_SNIPE_SIZE
+    + 1
+    + (32 * 4)
+    + 1
+    + 1
+    + END_ROUND_BOUNTY_SIZE
+    + 1
+    + AFFILIATE_RULES_SIZE
+    + 16
+    + 8
+    + LIMITER_SIZE
+    + AIRDROP_CONFIG_SIZE
+    + 1
+    + 1
+    + 1
+    + 1
+    + 1
+    + (TEAM_CONFIG_SIZE * MAX_TEAMS);
 #[allow(non_snake_case)]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct GameState {
@@ -23,66 +45,182 @@ pub struct GameState {
     pub round_init_time: i64,
     pub round_inc_time_per_key: i64,
     pub round_max_time: i64,
+    pub anti_snipe: AntiSnipe,
+    pub timing_mode: TimingMode, //unit of all of the above
     pub version: u64,
     pub mint: Pubkey,
+    pub decimals: u8, //of the mint, all amounts below are in its base units
     //privileged accounts
     pub game_creator: Pubkey,
     pub community_wallet: Pubkey,
     pub p3d_wallet: Pubkey,
+    //fees
+    pub exit_fee_percent: u8,
+    pub end_round_bounty: EndRoundBounty,
+    pub second_tier_affiliate: u8, //percent of each purchase paid to the affiliate's own affiliate
+    pub affiliate_rules: AffiliateRules,
+    pub name_fee: u128,  //paid to register a player name
+    pub name_count: u64, //names registered so far, the last one's id
+    pub limiter: Limiter,
+    pub airdrop: AirdropConfig,
+    pub randomness: RandomnessSource, //how the airdrop lottery is rolled
+    pub strong_rng: bool, //purchases must pass SlotHashes, to be mixed into the PseudoRng seed
+    pub top_level_purchases: bool, //purchases must pass Instructions, to prove they aren't made via CPI
+    pub auto_settle: bool, //the first purchase after the round is over ends it, instead of failing
+    //teams
+    pub team_count: u8, //only the first team_count entries below are in use
+    pub teams: [TeamConfig; MAX_TEAMS], //indexed by team
+}
+
+// Timestamp measures round windows in seconds of the clock's unix timestamp, which validators can skew.
+// Slot measures them in slots instead - round times and time settings are then all in slots.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub enum TimingMode {
+    Timestamp,
+    Slot,
+}
+
+impl TimingMode {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            TimingMode::Timestamp => "s",
+            TimingMode::Slot => "slots",
+        }
+    }
+}
+
+pub const ANTI_SNIPE_SIZE: usize = 8 + 8;
+// purchases made within the final `window` seconds (or slots) of the round extend it to at least
+// `min_time_left` remaining, however few keys they buy. Off when window is 0.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct AntiSnipe {
+    pub window: i64,
+    pub min_time_left: i64,
 }
 
 // --------------------------------------- fees & teams
 
-pub const FEE_SPLIT_SIZE: usize = 2;
-// when a key is purchased the fees are split between 1)next round, 2)f3d players, 3)p3d holders.
-// (1) can be deduced as 86 - (2)f3d - (3)p3d
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub const FEE_SPLIT_SIZE: usize = 7;
+// when a key is purchased the amount paid is split between all of the below (in %, adding up to 100).
+// pot is whatever stays in play to be split once the round is over
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
 pub struct FeeSplit {
+    pub community: u8,
+    pub airdrop: u8,
+    pub next_round: u8,
+    pub affiliate: u8,
     pub f3d: u8,
     pub p3d: u8,
+    pub pot: u8,
 }
 
-pub const POT_SPLIT_SIZE: usize = 2;
-// when the round is over the pot is split between 1)next round, 2)f3d players, 3)p3d holders.
-// (1) can be deduced as 50 - (2)f3d - (3)p3d
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub const POT_SPLIT_SIZE: usize = 5;
+// when the round is over the pot is split between all of the below (in %, adding up to 100).
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
 pub struct PotSplit {
+    pub winner: u8,
+    pub community: u8,
+    pub next_round: u8,
     pub f3d: u8,
     pub p3d: u8,
 }
 
-pub const TEAM_SIZE: usize = 1;
-#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
-pub enum Team {
-    Whale,
-    Bear,
-    Snek,
-    Bull,
+pub const END_ROUND_BOUNTY_SIZE: usize = 1 + 16;
+pub const MAX_END_ROUND_BOUNTY_PERCENT: u8 = 10;
+// paid out of the round's still-in-play funds to whoever ends the round: `percent` of them plus a
+// fixed `amount`, capped at MAX_END_ROUND_BOUNTY_PERCENT of them. Off when both are 0.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct EndRoundBounty {
+    pub percent: u8,
+    pub amount: u128,
 }
 
-pub const WHALE_FEE_SPLIT: FeeSplit = FeeSplit { f3d: 30, p3d: 6 };
-pub const BEAR_FEE_SPLIT: FeeSplit = FeeSplit { f3d: 43, p3d: 0 };
-pub const SNEK_FEE_SPLIT: FeeSplit = FeeSplit { f3d: 56, p3d: 10 };
-pub const BULL_FEE_SPLIT: FeeSplit = FeeSplit { f3d: 43, p3d: 8 };
+impl EndRoundBounty {
+    pub fn is_enabled(&self) -> bool {
+        self.percent > 0 || self.amount > 0
+    }
+}
 
-pub const WHALE_POT_SPLIT: PotSplit = PotSplit { f3d: 15, p3d: 10 };
-pub const BEAR_POT_SPLIT: PotSplit = PotSplit { f3d: 25, p3d: 0 };
-pub const SNEK_POT_SPLIT: PotSplit = PotSplit { f3d: 20, p3d: 20 };
-pub const BULL_POT_SPLIT: PotSplit = PotSplit { f3d: 30, p3d: 10 };
+pub const AFFILIATE_RULES_SIZE: usize = 1 + 16;
+// who can be credited with an affiliate share (never the buyer themselves). Anyone else when both are unset.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct AffiliateRules {
+    pub name_required: bool, //affiliate must be referred to by a registered name / id, not their account
+    pub min_keys: u128, //keys the affiliate must hold in the current round (same units as accum_keys)
+}
 
-pub const SOL_BY_TEAM_SIZE: usize = 16 * 4;
+pub const LIMITER_SIZE: usize = 1 + 16 + 16;
+// caps how much each player can put in early in the round, so that no one can hog the keys
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct Limiter {
+    pub enabled: bool,
+    pub pot_threshold: u128, //limiter applies while the round's pot is below this amount
+    pub player_cap: u128,    //max total amount a player can add while the limiter applies
+}
+
+pub const AIRDROP_TIER_SIZE: usize = 16 + 1;
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct AirdropTier {
+    pub min_stake: u128,    //purchases above this amount qualify for the tier
+    pub payout_percent: u8, //share of the airdrop pot won
+}
+
+pub const MAX_AIRDROP_TIERS: usize = 5;
+pub const AIRDROP_CONFIG_SIZE: usize =
+    16 + 8 + 8 + 32 + 32 + 8 + 1 + (AIRDROP_TIER_SIZE * MAX_AIRDROP_TIERS);
+// purchases above the min stake play in the airdrop lottery. Each one raises the odds of winning
+// by odds_step (in 0.1%) until someone wins, which resets them.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct AirdropConfig {
+    pub min_stake: u128,
+    pub odds_step: u64,
+    pub reveal_window: u64, //slots a commit-reveal / oracle entry has to be settled in, before it's forfeited
+    pub oracle: Pubkey, //trusted oracle that owns & writes the result account, for Oracle randomness
+    pub oracle_result: Pubkey, //the one result account entries are rolled on
+    pub oracle_max_age: u64, //slots after which an oracle result is considered stale
+    pub tier_count: u8, //only the first tier_count entries below are in use
+    pub tiers: [AirdropTier; MAX_AIRDROP_TIERS], //ordered by min stake, ascending
+}
+
+//SlotHashes only keeps the most recent 512 slots, so the commit slot has to be revealed before it drops out
+pub const MAX_REVEAL_WINDOW: u64 = 512;
+// PseudoRng rolls right away from the player's key and the clock - predictable, demo only.
+// CommitReveal has the player commit to hash(secret) when buying and roll later in RevealAirdrop,
+// mixing the secret with the hash of a slot that came after the commitment.
+// Oracle enters the purchase the same way and binds it to the next external VRF result (written by the
+// airdrop's oracle into its pinned result account) - rolled on the player's next purchase or in RevealAirdrop.
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy)]
+pub enum RandomnessSource {
+    PseudoRng,
+    CommitReveal,
+    Oracle,
+}
+
+pub const ORACLE_RESULT_SIZE: usize = 8 + 8 + 32;
+// layout the oracle's result account has to start with. Anything past it is ignored.
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
-pub struct SolByTeam {
-    pub whale: u128,
-    pub bear: u128,
-    pub snek: u128,
-    pub bull: u128,
+pub struct OracleResult {
+    pub seq: u64,  //incremented by the oracle with every result it publishes
+    pub slot: u64, //slot the randomness was published in
+    pub randomness: [u8; 32],
+}
+
+pub const MAX_TEAMS: usize = 8;
+pub const TEAM_CONFIG_SIZE: usize = FEE_SPLIT_SIZE + POT_SPLIT_SIZE;
+// each game declares its own roster of teams at init. Teams are referred to by their index
+// eg in original Fomo3D: 0 = whale, 1 = bear, 2 = snek, 3 = bull
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct TeamConfig {
+    pub fee_split: FeeSplit,
+    pub pot_split: PotSplit,
 }
 
 // --------------------------------------- round
 
 pub const ROUND_STATE_SIZE: usize =
-    1 + 8 + 32 + TEAM_SIZE + (8 * 2) + 1 + SOL_BY_TEAM_SIZE + (13 * 16) + 8;
+    1 + 8 + 32 + 1 + (8 * 2) + 1 + (16 * MAX_TEAMS) + (15 * 16) + 8 + 16 + 16;
+//profit per key is stored scaled up, so that small f3d shares spread over many keys don't round to 0
+pub const PROFIT_PER_KEY_PRECISION: u128 = 1_000_000_000_000_000_000;
 #[allow(non_snake_case)]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct RoundState {
@@ -90,15 +228,17 @@ pub struct RoundState {
     pub round_id: u64,
     //lead player
     pub lead_player_pk: Pubkey,
-    pub lead_player_team: Team,
+    pub lead_player_team: u8,
     //timing
+    //unix timestamps or slots, depending on the game's timing mode
     pub start_time: UnixTimestamp, //the time the round starts / has started
     pub end_time: UnixTimestamp,   //the time the round ends / has ended
     pub ended: bool,               //whether the round has ended
     //totals
     pub accum_keys: u128,
-    pub accum_sol_pot: u128, //in lamports
-    pub accum_sol_by_team: SolByTeam,
+    pub accum_sol_pot: u128,                  //in lamports
+    pub curve_position: u128, //sol the key curve has taken in, net of sales (no carry-over or fees)
+    pub accum_sol_by_team: [u128; MAX_TEAMS], //indexed by team
     //shares
     pub accum_community_share: u128,
     pub accum_airdrop_share: u128, //person who gets the airdrop wins part of this pot
@@ -108,17 +248,21 @@ pub struct RoundState {
     pub accum_f3d_share: u128, //sum of all f3d shares paid out to users (used for checks & balances)
     pub still_in_play: u128,
     pub final_prize_share: u128, //will be filled when round ends
+    pub bounty_paid: u128,       //paid to whoever ended the round
     //withdrawal history (used to offset any future attempts)
     pub withdrawn_com: u128,
     pub withdrawn_next_round: u128,
     pub withdrawn_p3d: u128,
     //airdrop
     pub airdrop_tracker: u64, //increment each time a qualified tx occurs
+    pub airdrop_won: u128, //sum of all airdrop prizes paid out to users (used for checks & balances)
+    //f3d dividends
+    pub profit_per_key: u128, //cumulative f3d earnings per key, scaled by PROFIT_PER_KEY_PRECISION
 }
 
 // --------------------------------------- player x round
 
-pub const PLAYER_ROUND_STATE_SIZE: usize = 1 + 32 + 8 + 32 + (7 * 16);
+pub const PLAYER_ROUND_STATE_SIZE: usize = 1 + 32 + 8 + 32 + 1 + (8 * 16) + 32 + 8 + 16 + 8 + 8;
 #[allow(non_snake_case)]
 #[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
 pub struct PlayerRoundState {
@@ -126,21 +270,106 @@ pub struct PlayerRoundState {
     pub player_pk: Pubkey,
     pub round_id: u64,
     pub last_affiliate_pk: Pubkey, //last person to refer the player
+    pub last_team: u8, //team of the player's last purchase, the one their key sales count against
     //totals
     pub accum_keys: u128,      //number of keys owned by the user
     pub accum_sol_added: u128, //amount of SOL the player has added to round (used as limiter)
     //shares (available for withdrawal to the user)
-    //NOTE: f3d share is calculated dynamically at the time of withdrawal due to constantly changing key ratio
+    //NOTE: f3d share is calculated at the time of withdrawal from round's profit per key, less f3d_mask
     pub accum_winnings: u128, //accumulated winnings from 1)the airdrop lottery, 2)the final prize
     pub accum_aff: u128,      //accumulated affiliate dividends
     //withdrawal history (used to offset any future attempts)
     pub withdrawn_winnings: u128,
     pub withdrawn_aff: u128,
     pub withdrawn_f3d: u128,
+    //f3d earnings the player is not entitled to (paid before they held the keys) or already withdrew
+    pub f3d_mask: u128,
+    //pending commit-reveal / oracle airdrop entry (stake is 0 when there's none)
+    pub airdrop_commitment: [u8; 32], //keccak hash of the player's secret, unused by oracle entries
+    pub airdrop_commit_slot: u64,
+    pub airdrop_stake: u128, //amount the entry was bought with, decides the prize tier
+    pub airdrop_odds: u64,   //airdrop tracker at the time of the commitment
+    pub airdrop_oracle_seq: u64, //oracle result seq at the time of an oracle entry, it's rolled on the next one
 }
 
 impl PlayerRoundState {
     pub fn has_affiliate_listed(&self) -> bool {
         !is_zero(&self.last_affiliate_pk.to_bytes())
     }
+
+    pub fn has_pending_airdrop(&self) -> bool {
+        self.airdrop_stake > 0
+    }
+
+    pub fn clear_pending_airdrop(&mut self) {
+        self.airdrop_commitment = [0; 32];
+        self.airdrop_commit_slot = 0;
+        self.airdrop_stake = 0;
+        self.airdrop_odds = 0;
+        self.airdrop_oracle_seq = 0;
+    }
+}
+
+// --------------------------------------- player
+
+pub const MAX_UNCLAIMED_ROUNDS: usize = 16;
+pub const PLAYER_STATE_SIZE: usize = 1 + 32 + 32 + 1 + (16 * 3) + 1 + (8 * MAX_UNCLAIMED_ROUNDS);
+// the player's profile across all rounds of a game version, kept up to date by PurchaseKeys / WithdrawSol
+#[allow(non_snake_case)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct PlayerState {
+    pub TYPE: StateType,
+    pub player_pk: Pubkey,
+    pub default_affiliate_pk: Pubkey, //affiliate of the player's last purchase, whichever round it was in
+    pub preferred_team: u8,           //team of the player's last purchase
+    //lifetime totals
+    pub lifetime_keys: u128,     //keys bought, not counting any sold back
+    pub lifetime_spend: u128,    //amount spent on keys
+    pub lifetime_winnings: u128, //airdrop / final prize winnings withdrawn
+    //rounds the player bought keys in or was credited in (affiliate / bounty), until they withdraw
+    //everything after the round ends.
+    //best-effort - once full, the oldest round is dropped (its balance stays withdrawable)
+    pub unclaimed_round_count: u8,
+    pub unclaimed_rounds: [u64; MAX_UNCLAIMED_ROUNDS], //only the first unclaimed_round_count are in use, oldest first
+}
+
+impl PlayerState {
+    pub fn has_default_affiliate(&self) -> bool {
+        !is_zero(&self.default_affiliate_pk.to_bytes())
+    }
+
+    pub fn unclaimed_rounds(&self) -> &[u64] {
+        &self.unclaimed_rounds[..self.unclaimed_round_count as usize]
+    }
+}
+
+// --------------------------------------- names
+
+//the name ends up in the name state's pda seed, which can't be longer than 32 bytes
+pub const MAX_NAME_LEN: usize = 20;
+pub const NAME_STATE_SIZE: usize = 1 + 32 + 8 + 1 + MAX_NAME_LEN;
+#[allow(non_snake_case)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct NameState {
+    pub TYPE: StateType,
+    pub player_pk: Pubkey, //player who registered the name
+    pub id: u64,           //numeric id assigned along with the name, see IdState
+    pub name_len: u8,
+    pub name: [u8; MAX_NAME_LEN], //normalized, only the first name_len bytes are in use
+}
+
+pub const ID_STATE_SIZE: usize = 1 + 32 + 8;
+// numeric alias of a registered name - both can be used as the player's affiliate code
+#[allow(non_snake_case)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone)]
+pub struct IdState {
+    pub TYPE: StateType,
+    pub player_pk: Pubkey,
+    pub id: u64,
+}
+
+impl NameState {
+    pub fn name(&self) -> &str {
+        std::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or_default()
+    }
 }
@@ -0,0 +1,90 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+//alice registers "satoshi" (id 1), bob "hal" (id 2)
+async fn codes_env() -> TestEnv {
+    let mut env = TestEnv::with_game(|_| {}).await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.register_name(&alice, &alice_acc, "satoshi")
+        .await
+        .unwrap();
+    env.register_name(&bob, &bob_acc, "hal").await.unwrap();
+    env
+}
+
+#[tokio::test]
+async fn test_registration_assigns_ids() {
+    let mut env = codes_env().await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    assert_eq!(env.game_state().await.name_count, 2);
+    assert_eq!(env.name_state("satoshi").await.id, 1);
+    assert_eq!(env.name_state("hal").await.id, 2);
+    assert_eq!(env.id_state(1).await.player_pk, alice.pubkey());
+    assert_eq!(env.id_state(2).await.player_pk, bob.pubkey());
+}
+
+#[tokio::test]
+async fn test_affiliate_by_name() {
+    let mut env = codes_env().await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let bob_acc = env.bob_token_acc;
+    let code = env.name_state_pk("satoshi");
+
+    env.purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &code)
+        .await
+        .unwrap();
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_aff,
+        sol(0.1)
+    );
+    assert_eq!(
+        env.player_round_state(&bob.pubkey())
+            .await
+            .last_affiliate_pk,
+        alice.pubkey()
+    );
+}
+
+#[tokio::test]
+async fn test_affiliate_by_id() {
+    let mut env = codes_env().await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+    let code = env.id_state_pk(2);
+
+    env.purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &bob.pubkey(), &code)
+        .await
+        .unwrap();
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_aff,
+        sol(0.1)
+    );
+}
+
+#[tokio::test]
+async fn test_affiliate_code_must_match_round_state() {
+    let mut env = codes_env().await;
+    let bob = clone_kp(&env.bob);
+    let bob_acc = env.bob_token_acc;
+    let creator = env.game_creator.pubkey();
+
+    //creator's round state, but alice's name
+    let code = env.name_state_pk("satoshi");
+    let err = env
+        .purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &creator, &code)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::PDAMatchFailure as u32);
+
+    //game accounts other than names / ids don't count as codes
+    let code = env.game_state_pk();
+    let err = env
+        .purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &creator, &code)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::WrongAccount as u32);
+}
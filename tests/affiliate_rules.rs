@@ -0,0 +1,130 @@
+mod utils;
+
+use fomo3d::{error::GameError, state::AffiliateRules};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_self_referral_rejected() {
+    let mut env = TestEnv::with_game(|_| {}).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let err = env
+        .purchase_keys_via_code(
+            &alice,
+            &alice_acc,
+            sol(1.0),
+            &alice.pubkey(),
+            &alice.pubkey(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::SelfReferral as u32);
+
+    //same when going through a registered name
+    env.register_name(&alice, &alice_acc, "satoshi")
+        .await
+        .unwrap();
+    let code = env.name_state_pk("satoshi");
+    let err = env
+        .purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &alice.pubkey(), &code)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::SelfReferral as u32);
+}
+
+#[tokio::test]
+async fn test_affiliate_name_required() {
+    let mut env = TestEnv::with_game(|params| {
+        params.affiliate_rules = AffiliateRules {
+            name_required: true,
+            min_keys: 0,
+        }
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    let err = env
+        .purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &alice.pubkey())
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::AffiliateNameRequired as u32);
+
+    env.register_name(&alice, &alice_acc, "satoshi")
+        .await
+        .unwrap();
+    let code = env.name_state_pk("satoshi");
+    env.purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &code)
+        .await
+        .unwrap();
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_aff,
+        sol(0.1)
+    );
+}
+
+#[tokio::test]
+async fn test_affiliate_min_keys() {
+    let mut env = TestEnv::with_game(|params| {
+        params.affiliate_rules = AffiliateRules {
+            name_required: false,
+            min_keys: 1,
+        }
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    //alice holds no keys yet
+    let err = env
+        .purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &alice.pubkey())
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::AffiliateBelowMinKeys as u32);
+
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &alice.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_aff,
+        sol(0.1)
+    );
+}
+
+#[tokio::test]
+async fn test_carried_over_affiliate_below_min_keys() {
+    let mut env = TestEnv::with_game(|params| {
+        params.affiliate_rules = AffiliateRules {
+            name_required: false,
+            min_keys: 1,
+        }
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    env.purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &bob.pubkey(), &bob.pubkey())
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+    env.init_round(2).await.unwrap();
+
+    //bob holds no keys in the new round, but alice can still buy - his share goes to p3d instead
+    env.purchase_keys_via_code(&alice, &alice_acc, sol(0.5), &bob.pubkey(), &bob.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(env.player_round_state(&bob.pubkey()).await.accum_aff, 0);
+    assert_eq!(env.round_state().await.accum_p3d_share, sol(0.05));
+    assert_eq!(
+        env.player_round_state(&alice.pubkey())
+            .await
+            .last_affiliate_pk,
+        bob.pubkey()
+    );
+}
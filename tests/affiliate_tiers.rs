@@ -0,0 +1,170 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+//games below pay 7% to the affiliate, 3% to the affiliate's own affiliate
+
+#[tokio::test]
+async fn test_second_tier_paid_to_affiliates_affiliate() {
+    let mut env = TestEnv::with_game(|params| params.second_tier_affiliate = 3).await;
+    let creator = clone_kp(&env.game_creator); //a third player, only ever referring others
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    //creator refers bob, bob refers alice
+    env.purchase_keys_via_code(
+        &bob,
+        &bob_acc,
+        sol(1.0),
+        &creator.pubkey(),
+        &creator.pubkey(),
+    )
+    .await
+    .unwrap();
+    env.purchase_keys_two_tier(
+        &alice,
+        &alice_acc,
+        sol(1.0),
+        &bob.pubkey(),
+        &creator.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_aff,
+        sol(0.07)
+    );
+    assert_eq!(
+        env.player_round_state(&creator.pubkey()).await.accum_aff,
+        sol(0.07) + sol(0.03)
+    );
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_aff_share, sol(0.07) * 2 + sol(0.03));
+    //only bob's purchase had nobody to pay the second tier to
+    assert_eq!(round_state.accum_p3d_share, sol(0.03));
+}
+
+#[tokio::test]
+async fn test_second_tier_carries_across_rounds() {
+    let mut env = TestEnv::with_game(|params| params.second_tier_affiliate = 3).await;
+    let creator = clone_kp(&env.game_creator); //a third player, only ever referring others
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    //creator refers bob in round 1, bob refers alice in round 2
+    env.purchase_keys_via_code(
+        &bob,
+        &bob_acc,
+        sol(1.0),
+        &creator.pubkey(),
+        &creator.pubkey(),
+    )
+    .await
+    .unwrap();
+    env.wait_for_round_to_end().await;
+    env.end_round(&bob.pubkey()).await.unwrap();
+    env.init_round(2).await.unwrap();
+    env.purchase_keys_two_tier(
+        &alice,
+        &alice_acc,
+        sol(1.0),
+        &bob.pubkey(),
+        &creator.pubkey(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        env.player_round_state(&creator.pubkey()).await.accum_aff,
+        sol(0.03)
+    );
+    assert_eq!(env.round_state().await.accum_p3d_share, 0);
+    //both credited players have round 2 listed, without buying in it
+    assert_eq!(
+        env.player_state(&bob.pubkey()).await.unclaimed_rounds(),
+        &[1, 2]
+    );
+    assert_eq!(
+        env.player_state(&creator.pubkey()).await.unclaimed_rounds(),
+        &[1, 2]
+    );
+}
+
+#[tokio::test]
+async fn test_unclaimed_second_tier_goes_to_p3d() {
+    let mut env = TestEnv::with_game(|params| params.second_tier_affiliate = 3).await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let bob_acc = env.bob_token_acc;
+
+    //alice has no affiliate of her own
+    env.purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &alice.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_aff,
+        sol(0.07)
+    );
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_aff_share, sol(0.07));
+    assert_eq!(round_state.accum_p3d_share, sol(0.03)); //team 1 pays no p3d fee
+}
+
+#[tokio::test]
+async fn test_second_tier_never_paid_back_to_buyer() {
+    let mut env = TestEnv::with_game(|params| params.second_tier_affiliate = 3).await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    //alice refers bob, then buys with bob as her affiliate
+    env.purchase_keys_via_code(&bob, &bob_acc, sol(1.0), &alice.pubkey(), &alice.pubkey())
+        .await
+        .unwrap();
+    env.purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &bob.pubkey(), &bob.pubkey())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_aff,
+        sol(0.07)
+    );
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_aff,
+        sol(0.07)
+    );
+    assert_eq!(env.round_state().await.accum_p3d_share, sol(0.03) * 2);
+}
+
+#[tokio::test]
+async fn test_second_tier_account_required() {
+    let mut env = TestEnv::with_game(|params| params.second_tier_affiliate = 3).await;
+    let creator = clone_kp(&env.game_creator); //a third player, only ever referring others
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    env.purchase_keys_via_code(
+        &bob,
+        &bob_acc,
+        sol(1.0),
+        &creator.pubkey(),
+        &creator.pubkey(),
+    )
+    .await
+    .unwrap();
+    let err = env
+        .purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &bob.pubkey(), &bob.pubkey())
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+}
+
+#[tokio::test]
+async fn test_second_tier_above_affiliate_share_rejected() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.second_tier_affiliate = 11;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidSplit as u32);
+}
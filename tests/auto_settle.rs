@@ -0,0 +1,119 @@
+mod utils;
+
+use fomo3d::{error::GameError, instruction::PurchaseOutcome, state::EndRoundBounty};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_purchase_after_timeout_settles_round() {
+    let mut env = TestEnv::with_game(|params| {
+        params.auto_settle = true;
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+
+    //the winner's account is needed to settle
+    let err = env
+        .purchase_keys(&bob, &bob_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+
+    let bob_balance = env.token_balance(&bob_acc).await;
+    env.settle_winner = Some(alice.pubkey());
+    let outcome = env
+        .purchase_keys_outcome(&bob, &bob_acc, sol(1.0))
+        .await
+        .unwrap();
+    assert_eq!(outcome, PurchaseOutcome::RoundSettled);
+    let round_state = env.round_state().await;
+    assert!(round_state.ended);
+    assert!(round_state.final_prize_share > 0);
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_winnings,
+        round_state.final_prize_share
+    );
+    //bob didn't buy anything and kept his tokens
+    assert_eq!(env.token_balance(&bob_acc).await, bob_balance);
+
+    //once settled, it's over as usual
+    env.settle_winner = None;
+    let err = env
+        .purchase_keys(&bob, &bob_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::AlreadyEnded as u32);
+    env.init_round(2).await.unwrap();
+    let outcome = env
+        .purchase_keys_outcome(&bob, &bob_acc, sol(1.0))
+        .await
+        .unwrap();
+    let keys = env.player_round_state(&bob.pubkey()).await.accum_keys;
+    assert_eq!(outcome, PurchaseOutcome::KeysPurchased { keys });
+}
+
+#[tokio::test]
+async fn test_purchase_settles_empty_round() {
+    let mut env = TestEnv::with_game(|params| {
+        params.auto_settle = true;
+    })
+    .await;
+    let bob = clone_kp(&env.bob);
+    let bob_acc = env.bob_token_acc;
+    env.wait_for_round_to_end().await;
+
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    assert!(env.round_state().await.ended);
+}
+
+#[tokio::test]
+async fn test_settling_buyer_collects_bounty() {
+    let mut env = TestEnv::with_game(|params| {
+        params.auto_settle = true;
+        params.end_round_bounty = EndRoundBounty {
+            percent: 1,
+            amount: 0,
+        };
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    let still_in_play = env.round_state().await.still_in_play;
+
+    env.settle_winner = Some(alice.pubkey());
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    assert_eq!(env.round_state().await.bounty_paid, still_in_play / 100);
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_winnings,
+        still_in_play / 100
+    );
+}
+
+#[tokio::test]
+async fn test_purchase_after_timeout_fails_otherwise() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+
+    let err = env
+        .purchase_keys(&bob, &bob_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::AlreadyEnded as u32);
+    assert!(!env.round_state().await.ended);
+}
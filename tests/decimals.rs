@@ -0,0 +1,71 @@
+mod utils;
+
+use solana_sdk::signature::Signer;
+use utils::*;
+
+async fn env_with_decimals(decimals: u8) -> TestEnv {
+    let mut env = TestEnv::new_with_decimals(decimals).await;
+    let tokens = |amount: f64| (amount * 10f64.powi(decimals as i32)) as u128;
+    env.start_game(|params| {
+        params.limiter.pot_threshold = tokens(100.0);
+        params.limiter.player_cap = tokens(1.0);
+        params.airdrop.min_stake = tokens(0.1);
+        for (i, amount) in [0.1, 1.0, 10.0].iter().enumerate() {
+            params.airdrop.tiers[i].min_stake = tokens(*amount);
+        }
+    })
+    .await;
+    env
+}
+
+#[tokio::test]
+async fn test_init_game_stores_decimals() {
+    let mut env = env_with_decimals(6).await;
+    assert_eq!(env.game_state().await.decimals, 6);
+}
+
+#[tokio::test]
+async fn test_same_keys_regardless_of_decimals() {
+    let mut keys = vec![];
+    for decimals in [6, 9, 12] {
+        let mut env = env_with_decimals(decimals).await;
+        let alice = clone_kp(&env.alice);
+        let alice_acc = env.alice_token_acc;
+        let amount = env.tokens(1.0);
+        env.purchase_keys(&alice, &alice_acc, amount).await.unwrap();
+        let round_state = env.round_state().await;
+        assert_eq!(round_state.accum_sol_pot, amount);
+        keys.push(round_state.accum_keys);
+    }
+    assert!(keys[0] > 0);
+    assert_eq!(keys[0], keys[1]);
+    assert_eq!(keys[1], keys[2]);
+}
+
+#[tokio::test]
+async fn test_airdrop_threshold_uses_decimals() {
+    //0.05 tokens is a lot of base units with 12 decimals, but still below the 0.1 token threshold
+    let mut env = env_with_decimals(12).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    let amount = env.tokens(0.05);
+    env.purchase_keys(&alice, &alice_acc, amount).await.unwrap();
+    assert_eq!(env.round_state().await.airdrop_tracker, 0);
+}
+
+#[tokio::test]
+async fn test_sell_keys_with_decimals() {
+    let mut env = env_with_decimals(6).await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    let amount = env.tokens(1.0);
+    env.purchase_keys(&alice, &alice_acc, amount).await.unwrap();
+    env.purchase_keys(&bob, &bob_acc, amount).await.unwrap();
+
+    let keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    let balance_before = env.token_balance(&alice_acc).await;
+    env.sell_keys(&alice, &alice_acc, keys / 4).await.unwrap();
+    let received = env.token_balance(&alice_acc).await - balance_before;
+    //a quarter of the keys is worth a fraction of a token, not millions of them
+    assert!(received > 0 && (received as u128) < env.tokens(1.0));
+}
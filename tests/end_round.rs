@@ -0,0 +1,296 @@
+mod utils;
+
+use fomo3d::{
+    error::GameError,
+    state::{EndRoundBounty, GameState, MAX_END_ROUND_BOUNTY_PERCENT},
+};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_empty_round_ends_without_winner() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+
+    let err = env.end_empty_round().await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::NotYetEnded as u32);
+
+    env.wait_for_round_to_end().await;
+    env.end_empty_round().await.unwrap();
+    assert!(env.round_state().await.ended);
+
+    //used to be stuck forever behind the unended empty round
+    env.init_round(2).await.unwrap();
+    assert_eq!(env.game_state().await.round_id, 2);
+}
+
+#[tokio::test]
+async fn test_empty_round_rolls_carry_over_forward() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+    let carry_over = env.round_state().await.accum_next_round_share;
+    assert!(carry_over > 0);
+
+    //nobody plays round 2 - the carried over funds are in play but have no winner
+    env.init_round(2).await.unwrap();
+    assert_eq!(env.round_state().await.still_in_play, carry_over);
+    env.wait_for_round_to_end().await;
+    env.end_empty_round().await.unwrap();
+    let round_state = env.round_state().await;
+    assert!(round_state.ended);
+    assert_eq!(round_state.still_in_play, 0);
+    assert_eq!(round_state.final_prize_share, 0);
+    assert_eq!(round_state.accum_next_round_share, carry_over);
+
+    env.init_round(3).await.unwrap();
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_sol_pot, carry_over);
+    assert_eq!(round_state.still_in_play, carry_over);
+    let pot = env.pot_pk(3);
+    assert_eq!(env.token_balance(&pot).await as u128, carry_over);
+}
+
+#[tokio::test]
+async fn test_round_with_buyers_needs_winner() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+
+    let err = env.end_empty_round().await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+
+    env.end_round(&alice.pubkey()).await.unwrap();
+    let err = env.end_round(&alice.pubkey()).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::AlreadyEnded as u32);
+}
+
+#[tokio::test]
+async fn test_end_and_advance_in_one_go() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+
+    let err = env
+        .end_and_advance(Some(&alice.pubkey()))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::NotYetEnded as u32);
+    assert_eq!(env.round, 1);
+
+    env.wait_for_round_to_end().await;
+    env.end_and_advance(Some(&alice.pubkey())).await.unwrap();
+    assert_eq!(env.round, 2);
+    assert_eq!(env.game_state().await.round_id, 2);
+    let previous_round_state = env.round_state_for(1).await;
+    assert!(previous_round_state.ended);
+    assert!(previous_round_state.final_prize_share > 0);
+    let carry_over = previous_round_state.accum_next_round_share;
+    assert_eq!(previous_round_state.withdrawn_next_round, carry_over);
+
+    let round_state = env.round_state().await;
+    assert!(!round_state.ended);
+    assert_eq!(round_state.still_in_play, carry_over);
+    let pot = env.pot_pk(2);
+    assert_eq!(env.token_balance(&pot).await as u128, carry_over);
+
+    //nobody plays round 2, no winner account needed
+    env.wait_for_round_to_end().await;
+    env.end_and_advance(None).await.unwrap();
+    assert_eq!(env.round_state().await.still_in_play, carry_over);
+}
+
+#[tokio::test]
+async fn test_end_and_advance_after_separate_end() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    env.wait_for_round_to_end().await;
+    env.end_empty_round().await.unwrap();
+
+    env.end_and_advance(None).await.unwrap();
+    assert_eq!(env.game_state().await.round_id, 2);
+    assert!(!env.round_state().await.ended);
+}
+
+//what's left for the winner once the rest of the pot has been split (purchases are made by team 1)
+fn grand_prize(game_state: &GameState, to_be_divided: u128) -> u128 {
+    let split = game_state.teams[1].pot_split;
+    let share = |percent: u8| to_be_divided * percent as u128 / 100;
+    to_be_divided
+        - share(split.community)
+        - share(split.f3d)
+        - share(split.p3d)
+        - share(split.next_round)
+}
+
+#[tokio::test]
+async fn test_bounty_goes_to_caller() {
+    let mut env = TestEnv::with_game(|params| {
+        params.end_round_bounty = EndRoundBounty {
+            percent: 1,
+            amount: 0,
+        }
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+
+    //the caller's accounts are mandatory
+    let err = env.end_round(&alice.pubkey()).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+
+    let still_in_play = env.round_state().await.still_in_play;
+    env.end_round_by(Some(&bob), Some(&alice.pubkey()))
+        .await
+        .unwrap();
+    let bounty = still_in_play / 100;
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.bounty_paid, bounty);
+    let game_state = env.game_state().await;
+    assert_eq!(
+        round_state.final_prize_share,
+        grand_prize(&game_state, still_in_play - bounty)
+    );
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_winnings,
+        bounty
+    );
+    assert_eq!(
+        env.player_state(&bob.pubkey()).await.unclaimed_rounds(),
+        &[1]
+    );
+
+    //bob never played, but can still withdraw it
+    let balance_before = env.token_balance(&bob_acc).await;
+    env.withdraw_sol(&bob, &bob_acc).await.unwrap();
+    assert_eq!(
+        env.token_balance(&bob_acc).await,
+        balance_before + bounty as u64
+    );
+    assert!(env
+        .player_state(&bob.pubkey())
+        .await
+        .unclaimed_rounds()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_winner_can_collect_bounty() {
+    let mut env = TestEnv::with_game(|params| {
+        params.end_round_bounty = EndRoundBounty {
+            percent: 0,
+            amount: sol(0.02),
+        }
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    let still_in_play = env.round_state().await.still_in_play;
+
+    env.end_round_by(Some(&alice), Some(&alice.pubkey()))
+        .await
+        .unwrap();
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.bounty_paid, sol(0.02));
+    assert_eq!(
+        env.player_round_state(&alice.pubkey()).await.accum_winnings,
+        round_state.final_prize_share + sol(0.02)
+    );
+    let game_state = env.game_state().await;
+    assert_eq!(
+        round_state.final_prize_share,
+        grand_prize(&game_state, still_in_play - sol(0.02))
+    );
+}
+
+#[tokio::test]
+async fn test_bounty_is_capped_at_max_percent() {
+    let mut env = TestEnv::with_game(|params| {
+        params.end_round_bounty = EndRoundBounty {
+            percent: 5,
+            amount: sol(100.0),
+        }
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    let still_in_play = env.round_state().await.still_in_play;
+
+    env.end_round_by(Some(&bob), Some(&alice.pubkey()))
+        .await
+        .unwrap();
+    let bounty = still_in_play * MAX_END_ROUND_BOUNTY_PERCENT as u128 / 100;
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.bounty_paid, bounty);
+    let game_state = env.game_state().await;
+    assert_eq!(
+        round_state.final_prize_share,
+        grand_prize(&game_state, still_in_play - bounty)
+    );
+}
+
+#[tokio::test]
+async fn test_end_and_advance_pays_bounty_to_funder() {
+    let mut env = TestEnv::with_game(|params| {
+        params.end_round_bounty = EndRoundBounty {
+            percent: 1,
+            amount: 0,
+        }
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    let still_in_play = env.round_state().await.still_in_play;
+
+    env.end_and_advance(Some(&alice.pubkey())).await.unwrap();
+    let funder = env.game_creator.pubkey();
+    assert_eq!(
+        env.player_round_state_for(&funder, 1).await.accum_winnings,
+        still_in_play / 100
+    );
+}
+
+#[tokio::test]
+async fn test_init_refuses_large_bounty() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.end_round_bounty.percent = 11;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::AboveThreshold as u32);
+}
@@ -0,0 +1,68 @@
+mod utils;
+
+use fomo3d::state::PROFIT_PER_KEY_PRECISION;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_late_buyer_does_not_earn_past_dividends() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    let profit_per_key_before = env.round_state().await.profit_per_key;
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    let round_state = env.round_state().await;
+    let bob_keys = env.player_round_state(&bob.pubkey()).await.accum_keys;
+
+    //bob only earns from his own purchase onwards
+    let bob_balance = env.token_balance(&bob_acc).await;
+    env.withdraw_sol(&bob, &bob_acc).await.unwrap();
+    let bob_f3d = (env.token_balance(&bob_acc).await - bob_balance) as u128;
+    assert_eq!(
+        bob_f3d,
+        (round_state.profit_per_key - profit_per_key_before) * bob_keys / PROFIT_PER_KEY_PRECISION
+    );
+
+    //alice earns from both purchases, and together they take out what was paid in (give or take rounding)
+    let alice_balance = env.token_balance(&alice_acc).await;
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    let alice_f3d = (env.token_balance(&alice_acc).await - alice_balance) as u128;
+    assert!(alice_f3d > bob_f3d);
+    let total_f3d = alice_f3d + bob_f3d;
+    assert!(
+        total_f3d.max(round_state.accum_f3d_share) - total_f3d.min(round_state.accum_f3d_share)
+            <= 2
+    );
+}
+
+#[tokio::test]
+async fn test_withdraw_f3d_before_and_after_new_purchases() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    let withdrawn_first = env.player_round_state(&alice.pubkey()).await.withdrawn_f3d;
+    assert!(withdrawn_first > 0);
+
+    //previously this would underflow, as alice's share of the (now larger) key pool got smaller
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    let alice_state = env.player_round_state(&alice.pubkey()).await;
+    assert!(alice_state.withdrawn_f3d > withdrawn_first);
+    assert_eq!(alice_state.f3d_mask, alice_state.withdrawn_f3d);
+
+    //nothing left to withdraw
+    let alice_balance = env.token_balance(&alice_acc).await;
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    assert_eq!(env.token_balance(&alice_acc).await, alice_balance);
+}
@@ -0,0 +1,135 @@
+mod utils;
+
+use fomo3d::{
+    error::GameError,
+    state::{FeeSplit, PotSplit, TeamConfig, MAX_TEAMS},
+};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_init_game_stores_teams() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    let game_state = env.game_state().await;
+    assert_eq!(game_state.team_count, 4);
+    assert_eq!(&game_state.teams[..4], &default_teams()[..]);
+    assert_eq!(game_state.teams[4], TeamConfig::default());
+}
+
+#[tokio::test]
+async fn test_init_game_refuses_fee_split_not_adding_up() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.teams[2].fee_split.pot += 1;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidSplit as u32);
+}
+
+#[tokio::test]
+async fn test_init_game_refuses_pot_split_not_adding_up() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.teams[0].pot_split.winner -= 1;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidSplit as u32);
+}
+
+#[tokio::test]
+async fn test_init_game_refuses_invalid_team_count() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.teams = vec![];
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidTeamCount as u32);
+
+    let mut params = default_game_params(env.version);
+    params.teams = vec![team(30, 6, 15, 10); MAX_TEAMS + 1];
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidTeamCount as u32);
+}
+
+#[tokio::test]
+async fn test_init_game_refuses_invalid_airdrop_tiers() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.airdrop.tiers.swap(0, 1);
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropConfig as u32);
+
+    let mut params = default_game_params(env.version);
+    params.airdrop.tiers[2].payout_percent = 101;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropConfig as u32);
+}
+
+#[tokio::test]
+async fn test_airdrop_min_stake_is_used() {
+    let mut env = TestEnv::with_game(|params| {
+        params.airdrop.min_stake = sol(0.5);
+        params.airdrop.odds_step = 3;
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    //below the min stake - doesn't play
+    env.purchase_keys(&alice, &alice_acc, sol(0.4))
+        .await
+        .unwrap();
+    assert_eq!(env.round_state().await.airdrop_tracker, 0);
+    //above it - raises the odds by the configured step (unless it wins and resets them)
+    env.purchase_keys(&bob, &bob_acc, sol(0.6)).await.unwrap();
+    let round_state = env.round_state().await;
+    assert!(round_state.airdrop_tracker == 3 || round_state.airdrop_won > 0);
+}
+
+#[tokio::test]
+async fn test_custom_teams_are_used() {
+    let mut env = TestEnv::with_game(|params| {
+        params.teams = vec![TeamConfig {
+            fee_split: FeeSplit {
+                community: 10,
+                pot: 90,
+                ..FeeSplit::default()
+            },
+            pot_split: PotSplit {
+                winner: 80,
+                next_round: 20,
+                ..PotSplit::default()
+            },
+        }];
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    let ix = env.purchase_keys_ix(&alice.pubkey(), &alice_acc, sol(1.0), 0, None);
+    env.process_tx(&[ix], &[&alice]).await.unwrap();
+
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_sol_by_team[0], sol(1.0));
+    assert_eq!(round_state.accum_community_share, sol(0.1));
+    assert_eq!(round_state.accum_airdrop_share, 0);
+    assert_eq!(round_state.accum_p3d_share, 0);
+    assert_eq!(round_state.accum_f3d_share, 0);
+    assert_eq!(round_state.still_in_play, sol(0.9));
+
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.final_prize_share, sol(0.72));
+    assert_eq!(round_state.accum_next_round_share, sol(0.18));
+    assert_eq!(round_state.accum_community_share, sol(0.1));
+}
+
+#[tokio::test]
+async fn test_purchase_refuses_unknown_team() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    //only 4 teams in the default game
+    let ix = env.purchase_keys_ix(&alice.pubkey(), &alice_acc, sol(1.0), 4, None);
+    let err = env.process_tx(&[ix], &[&alice]).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidTeam as u32);
+}
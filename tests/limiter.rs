@@ -0,0 +1,77 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_limiter_clips_purchase() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    let balance_before = env.token_balance(&alice_acc).await;
+    env.purchase_keys(&alice, &alice_acc, sol(1.5))
+        .await
+        .unwrap();
+
+    //only the capped amount is taken from the player
+    let alice_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.accum_sol_added, sol(1.0));
+    assert_eq!(
+        env.token_balance(&alice_acc).await,
+        balance_before - sol(1.0) as u64
+    );
+}
+
+#[tokio::test]
+async fn test_limiter_refuses_purchase_once_cap_reached() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::PurchaseLimitReached as u32);
+}
+
+#[tokio::test]
+async fn test_limiter_can_be_disabled() {
+    let mut env = TestEnv::with_game(|params| {
+        params.limiter.enabled = false;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.5))
+        .await
+        .unwrap();
+
+    let alice_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.accum_sol_added, sol(1.5));
+}
+
+#[tokio::test]
+async fn test_limiter_lifts_above_pot_threshold() {
+    let mut env = TestEnv::with_game(|params| {
+        params.limiter.pot_threshold = sol(1.0);
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    env.purchase_keys(&alice, &alice_acc, sol(2.0))
+        .await
+        .unwrap();
+
+    let alice_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.accum_sol_added, sol(2.0));
+}
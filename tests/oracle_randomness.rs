@@ -0,0 +1,249 @@
+mod utils;
+
+use fomo3d::{error::GameError, state::RandomnessSource};
+use solana_program::{clock::Clock, pubkey::Pubkey};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+const ORACLE_MAX_AGE: u64 = 10;
+
+//an odds step of 1000 (100%) means every roll wins, which keeps the tests deterministic
+
+//(!) write the result after any slot warps - the stand-in account's lamports come out of thin air,
+//which the bank's capitalization check on warp doesn't like
+async fn publish(env: &mut TestEnv, oracle: &Pubkey, seq: u64, age: u64) {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    let result = env.game_state().await.airdrop.oracle_result;
+    env.write_oracle_result(&result, oracle, seq, clock.slot - age, [9; 32]);
+    env.oracle_result = Some(result);
+}
+
+#[tokio::test]
+async fn test_oracle_roll_settles_airdrop() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    publish(&mut env, &oracle, 1, 0).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    //the result alice can already see doesn't decide her entry
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    let airdrop_pot = env.round_state().await.accum_airdrop_share;
+    assert_eq!(airdrop_pot, sol(0.01));
+    assert!(env
+        .player_round_state(&alice.pubkey())
+        .await
+        .has_pending_airdrop());
+    let err = env.reveal_airdrop(&alice, [0; 32]).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropReveal as u32);
+
+    //the next one does
+    env.warp_slots(1).await;
+    publish(&mut env, &oracle, 2, 0).await;
+    env.reveal_airdrop(&alice, [0; 32]).await.unwrap();
+
+    let prize = airdrop_pot * 25 / 100;
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.airdrop_won, prize);
+    assert_eq!(round_state.accum_airdrop_share, airdrop_pot - prize);
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.accum_winnings, prize);
+    assert!(!player_round_state.has_pending_airdrop());
+}
+
+#[tokio::test]
+async fn test_oracle_entry_settled_by_next_purchase() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    publish(&mut env, &oracle, 1, 0).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    env.purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap();
+    env.warp_slots(1).await;
+    publish(&mut env, &oracle, 2, 0).await;
+    env.purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap();
+
+    //the first entry won a share of the pot as it was then, the second one is waiting its turn
+    let prize = sol(0.005) * 25 / 100;
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.airdrop_won, prize);
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.accum_winnings, prize);
+    assert!(player_round_state.has_pending_airdrop());
+    assert_eq!(player_round_state.airdrop_stake, sol(0.5));
+}
+
+#[tokio::test]
+async fn test_oracle_refuses_stale_result() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    env.warp_slots(ORACLE_MAX_AGE + 1).await;
+    publish(&mut env, &oracle, 1, ORACLE_MAX_AGE + 1).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::StaleRandomness as u32);
+
+    //purchases too small for the airdrop don't need fresh randomness
+    env.purchase_keys(&alice, &alice_acc, sol(0.05))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_oracle_refuses_untrusted_result() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    let impostor = Pubkey::new_unique();
+    publish(&mut env, &impostor, 1, 0).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidOwner as u32);
+}
+
+#[tokio::test]
+async fn test_oracle_refuses_unpinned_result() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    //written by the right oracle, but not into the result account the game rolls on
+    let result = Pubkey::new_unique();
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    env.write_oracle_result(&result, &oracle, 1, clock.slot, [9; 32]);
+    env.oracle_result = Some(result);
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::WrongAccount as u32);
+}
+
+#[tokio::test]
+async fn test_oracle_later_result_forfeits_entry() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    publish(&mut env, &oracle, 1, 0).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    assert_eq!(
+        env.player_round_state(&alice.pubkey())
+            .await
+            .airdrop_oracle_seq,
+        1
+    );
+
+    //alice sat on result 2 - result 3 would win too, but her entry was bound to the one before it
+    env.warp_slots(1).await;
+    publish(&mut env, &oracle, 3, 0).await;
+    env.reveal_airdrop(&alice, [0; 32]).await.unwrap();
+
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.airdrop_won, 0);
+    assert_eq!(round_state.accum_airdrop_share, sol(0.01));
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.accum_winnings, 0);
+    assert!(!player_round_state.has_pending_airdrop());
+}
+
+#[tokio::test]
+async fn test_oracle_result_is_mandatory() {
+    let oracle = Pubkey::new_unique();
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::Oracle;
+        params.airdrop.oracle = oracle;
+        params.airdrop.oracle_result = Pubkey::new_unique();
+        params.airdrop.oracle_max_age = ORACLE_MAX_AGE;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(env.round_state().await.accum_keys, 0);
+}
+
+#[tokio::test]
+async fn test_init_refuses_missing_oracle() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.randomness = RandomnessSource::Oracle;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropConfig as u32);
+}
+
+#[tokio::test]
+async fn test_init_refuses_missing_oracle_result() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.randomness = RandomnessSource::Oracle;
+    params.airdrop.oracle = Pubkey::new_unique();
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropConfig as u32);
+}
@@ -0,0 +1,127 @@
+mod utils;
+
+use fomo3d::{error::GameError, processor::util::calculate_player_claimable};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_purchase_updates_player_state() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+
+    env.purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &bob.pubkey(), &bob.pubkey())
+        .await
+        .unwrap();
+
+    let player_state = env.player_state(&alice.pubkey()).await;
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_state.player_pk, alice.pubkey());
+    assert_eq!(player_state.default_affiliate_pk, bob.pubkey());
+    assert_eq!(player_state.preferred_team, 1);
+    assert_eq!(player_state.lifetime_keys, player_round_state.accum_keys);
+    assert_eq!(player_state.lifetime_spend, sol(1.0));
+    assert_eq!(player_state.lifetime_winnings, 0);
+    assert_eq!(player_state.unclaimed_rounds(), &[1]);
+
+    //withdrawing mid-round keeps the round listed, the player's keys keep earning
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    assert_eq!(
+        env.player_state(&alice.pubkey()).await.unclaimed_rounds(),
+        &[1]
+    );
+}
+
+#[tokio::test]
+async fn test_player_state_carries_across_rounds() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+
+    env.purchase_keys_via_code(&alice, &alice_acc, sol(1.0), &bob.pubkey(), &bob.pubkey())
+        .await
+        .unwrap();
+    let round_1_keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+
+    //cashing out of an ended round drops it from the list
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    let winnings = env.player_round_state(&alice.pubkey()).await.accum_winnings;
+    assert!(winnings > 0);
+    let player_state = env.player_state(&alice.pubkey()).await;
+    assert_eq!(player_state.lifetime_winnings, winnings);
+    assert!(player_state.unclaimed_rounds().is_empty());
+
+    //the default affiliate carries over to the new round, and has to be passed
+    env.init_round(2).await.unwrap();
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+    let ix = env.purchase_keys_ix(
+        &alice.pubkey(),
+        &alice_acc,
+        sol(0.5),
+        1,
+        Some(&bob.pubkey()),
+    );
+    env.process_tx(&[ix], &[&alice]).await.unwrap();
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_aff,
+        sol(0.05)
+    );
+    let round_2_keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    let player_state = env.player_state(&alice.pubkey()).await;
+    assert_eq!(player_state.default_affiliate_pk, bob.pubkey());
+    assert_eq!(player_state.lifetime_keys, round_1_keys + round_2_keys);
+    assert_eq!(player_state.lifetime_spend, sol(1.5));
+    assert_eq!(player_state.lifetime_winnings, winnings);
+    assert_eq!(player_state.unclaimed_rounds(), &[2]);
+}
+
+#[tokio::test]
+async fn test_reload_updates_player_state() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    let round_1_keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+    let winnings = env.player_round_state(&alice.pubkey()).await.accum_winnings;
+    let to_reload = winnings / 2;
+    env.init_round(2).await.unwrap();
+
+    //part of round 1's balance is left, so it stays listed
+    env.reload_keys(&alice, to_reload, 1).await.unwrap();
+    let round_2_keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    let player_state = env.player_state(&alice.pubkey()).await;
+    assert_eq!(player_state.lifetime_keys, round_1_keys + round_2_keys);
+    assert_eq!(player_state.lifetime_spend, sol(1.0) + to_reload);
+    assert_eq!(player_state.lifetime_winnings, to_reload);
+    assert_eq!(player_state.unclaimed_rounds(), &[1, 2]);
+
+    //reloading the rest drops it
+    let left = calculate_player_claimable(
+        &env.player_round_state_for(&alice.pubkey(), 1).await,
+        &env.round_state_for(1).await,
+    )
+    .unwrap()
+    .total()
+    .unwrap();
+    env.reload_keys(&alice, left, 1).await.unwrap();
+    let player_state = env.player_state(&alice.pubkey()).await;
+    assert_eq!(player_state.lifetime_winnings, winnings);
+    assert_eq!(player_state.unclaimed_rounds(), &[2]);
+}
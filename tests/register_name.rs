@@ -0,0 +1,70 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_register_name() {
+    let mut env = TestEnv::with_game(|_| {}).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    let balance_before = env.token_balance(&alice_acc).await;
+
+    env.register_name(&alice, &alice_acc, "Satoshi 21")
+        .await
+        .unwrap();
+    let name_state = env.name_state("satoshi 21").await;
+    assert_eq!(name_state.player_pk, alice.pubkey());
+    assert_eq!(name_state.name(), "satoshi 21");
+
+    //the fee goes to the community
+    assert_eq!(
+        env.token_balance(&alice_acc).await,
+        balance_before - NAME_FEE as u64
+    );
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_community_share, NAME_FEE);
+    assert_eq!(round_state.accum_sol_pot, NAME_FEE);
+    assert_eq!(round_state.curve_position, 0);
+    let pot = env.pot_pk(1);
+    assert_eq!(env.token_balance(&pot).await as u128, NAME_FEE);
+}
+
+#[tokio::test]
+async fn test_names_are_unique() {
+    let mut env = TestEnv::with_game(|_| {}).await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.register_name(&alice, &alice_acc, "satoshi")
+        .await
+        .unwrap();
+
+    //case doesn't make a different name
+    let err = env
+        .register_name(&bob, &bob_acc, "SATOSHI")
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::NameTaken as u32);
+    assert_eq!(env.name_state("satoshi").await.player_pk, alice.pubkey());
+
+    //a player can have more than one name
+    env.register_name(&alice, &alice_acc, "hal").await.unwrap();
+    assert_eq!(env.round_state().await.accum_community_share, NAME_FEE * 2);
+}
+
+#[tokio::test]
+async fn test_register_name_refuses_invalid_names() {
+    let mut env = TestEnv::with_game(|_| {}).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    for name in ["0xsatoshi", "sat-oshi", "12345", " satoshi", "sat  oshi"] {
+        let err = env
+            .register_name(&alice, &alice_acc, name)
+            .await
+            .unwrap_err();
+        assert_eq!(custom_error(err), GameError::InvalidName as u32);
+    }
+    assert_eq!(env.round_state().await.accum_community_share, 0);
+}
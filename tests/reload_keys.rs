@@ -0,0 +1,125 @@
+mod utils;
+
+use fomo3d::{error::GameError, state::PROFIT_PER_KEY_PRECISION};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_reload_keys_from_current_round() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+    //bob earns 0.1 sol as alice's affiliate
+    let ix = env.purchase_keys_ix(
+        &alice.pubkey(),
+        &alice_acc,
+        sol(1.0),
+        1,
+        Some(&bob.pubkey()),
+    );
+    env.process_tx(&[ix], &[&alice]).await.unwrap();
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_aff,
+        sol(0.1)
+    );
+
+    let pot = env.pot_pk(1);
+    let pot_before = env.token_balance(&pot).await;
+    let round_before = env.round_state().await;
+    env.reload_keys(&bob, sol(0.05), 1).await.unwrap();
+
+    //no tokens move, the reloaded amount is simply marked as withdrawn
+    assert_eq!(env.token_balance(&pot).await, pot_before);
+    let bob_state = env.player_round_state(&bob.pubkey()).await;
+    assert!(bob_state.accum_keys > 0);
+    assert_eq!(bob_state.accum_sol_added, sol(0.05));
+    assert_eq!(bob_state.withdrawn_aff, sol(0.05));
+    let round_after = env.round_state().await;
+    assert_eq!(
+        round_after.accum_sol_pot,
+        round_before.accum_sol_pot + sol(0.05)
+    );
+    assert_eq!(
+        round_after.accum_keys,
+        round_before.accum_keys + bob_state.accum_keys
+    );
+    assert_eq!(round_after.lead_player_pk, bob.pubkey());
+}
+
+#[tokio::test]
+async fn test_reload_keys_refuses_more_than_claimable() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+    let ix = env.purchase_keys_ix(
+        &alice.pubkey(),
+        &alice_acc,
+        sol(1.0),
+        1,
+        Some(&bob.pubkey()),
+    );
+    env.process_tx(&[ix], &[&alice]).await.unwrap();
+
+    let err = env.reload_keys(&bob, sol(0.2), 1).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InsufficientFunds as u32);
+    assert_eq!(env.player_round_state(&bob.pubkey()).await.withdrawn_aff, 0);
+}
+
+#[tokio::test]
+async fn test_reload_keys_from_previous_round() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+    let winnings = env.player_round_state(&alice.pubkey()).await.accum_winnings;
+    let to_reload = winnings / 2;
+    assert!(to_reload > 0);
+    env.init_round(2).await.unwrap();
+
+    let (pot1, pot2) = (env.pot_pk(1), env.pot_pk(2));
+    let pot1_before = env.token_balance(&pot1).await;
+    let pot2_before = env.token_balance(&pot2).await;
+    let round2_before = env.round_state().await;
+    env.reload_keys(&alice, to_reload, 1).await.unwrap();
+
+    //tokens move from round 1's pot over to round 2's
+    assert_eq!(
+        env.token_balance(&pot1).await,
+        pot1_before - to_reload as u64
+    );
+    assert_eq!(
+        env.token_balance(&pot2).await,
+        pot2_before + to_reload as u64
+    );
+    let source_state = env.player_round_state_for(&alice.pubkey(), 1).await;
+    assert_eq!(source_state.withdrawn_winnings, to_reload);
+    let alice_state = env.player_round_state(&alice.pubkey()).await;
+    assert!(alice_state.accum_keys > 0);
+    assert_eq!(alice_state.accum_sol_added, to_reload);
+    assert_eq!(
+        env.round_state().await.accum_sol_pot,
+        round2_before.accum_sol_pot + to_reload
+    );
+
+    //the remainder (incl. f3d earnings) can still be withdrawn as usual
+    env.round = 1;
+    let f3d_share = env.round_state().await.profit_per_key
+        * env.player_round_state(&alice.pubkey()).await.accum_keys
+        / PROFIT_PER_KEY_PRECISION;
+    let balance_before = env.token_balance(&alice_acc).await;
+    env.withdraw_sol(&alice, &alice_acc).await.unwrap();
+    assert_eq!(
+        env.token_balance(&alice_acc).await as u128,
+        balance_before as u128 + winnings - to_reload + f3d_share
+    );
+}
@@ -0,0 +1,151 @@
+mod utils;
+
+use fomo3d::{error::GameError, state::RandomnessSource};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+const SECRET: [u8; 32] = [7; 32];
+
+//an odds step of 1000 (100%) means every revealed entry wins, which keeps the tests deterministic
+
+#[tokio::test]
+async fn test_reveal_settles_airdrop() {
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::CommitReveal;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.commit_purchase_keys(&alice, &alice_acc, sol(1.0), SECRET)
+        .await
+        .unwrap();
+
+    //nothing is won at purchase time, the entry waits for the reveal
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.accum_winnings, 0);
+    assert_eq!(
+        player_round_state.airdrop_commitment,
+        solana_program::keccak::hash(&SECRET).to_bytes()
+    );
+    assert_eq!(player_round_state.airdrop_stake, sol(1.0));
+    assert_eq!(player_round_state.airdrop_odds, 1000);
+
+    //no slot after the commitment has been recorded yet
+    let err = env.reveal_airdrop(&alice, SECRET).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropReveal as u32);
+
+    env.warp_slots(5).await;
+    let airdrop_pot = env.round_state().await.accum_airdrop_share;
+    env.reveal_airdrop(&alice, SECRET).await.unwrap();
+
+    //1 sol is above the 0.1 sol tier, but not above the 1 sol one
+    let prize = airdrop_pot * 25 / 100;
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_airdrop_share, airdrop_pot - prize);
+    assert_eq!(round_state.airdrop_won, prize);
+    assert_eq!(round_state.airdrop_tracker, 0);
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.accum_winnings, prize);
+    assert!(!player_round_state.has_pending_airdrop());
+
+    //the entry can only be revealed once
+    let err = env.reveal_airdrop(&alice, SECRET).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::NoPendingAirdrop as u32);
+}
+
+#[tokio::test]
+async fn test_reveal_refuses_wrong_secret() {
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::CommitReveal;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.commit_purchase_keys(&alice, &alice_acc, sol(1.0), SECRET)
+        .await
+        .unwrap();
+    env.warp_slots(5).await;
+
+    let err = env.reveal_airdrop(&alice, [8; 32]).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropReveal as u32);
+    assert!(env
+        .player_round_state(&alice.pubkey())
+        .await
+        .has_pending_airdrop());
+}
+
+#[tokio::test]
+async fn test_expired_reveal_is_forfeited() {
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::CommitReveal;
+        params.airdrop.odds_step = 1000;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.commit_purchase_keys(&alice, &alice_acc, sol(1.0), SECRET)
+        .await
+        .unwrap();
+    env.warp_slots(REVEAL_WINDOW + 1).await;
+
+    let airdrop_pot = env.round_state().await.accum_airdrop_share;
+    env.reveal_airdrop(&alice, SECRET).await.unwrap();
+    assert_eq!(env.round_state().await.accum_airdrop_share, airdrop_pot);
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.accum_winnings, 0);
+    assert!(!player_round_state.has_pending_airdrop());
+}
+
+#[tokio::test]
+async fn test_pending_reveal_blocks_new_commitment() {
+    let mut env = TestEnv::with_game(|params| {
+        params.randomness = RandomnessSource::CommitReveal;
+        params.airdrop.odds_step = 1;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.commit_purchase_keys(&alice, &alice_acc, sol(0.2), SECRET)
+        .await
+        .unwrap();
+
+    let err = env
+        .commit_purchase_keys(&alice, &alice_acc, sol(0.3), [8; 32])
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::AirdropRevealPending as u32);
+
+    //buying without a commitment is still fine, and doesn't touch the pending entry
+    env.purchase_keys(&alice, &alice_acc, sol(0.3))
+        .await
+        .unwrap();
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.airdrop_stake, sol(0.2));
+    assert_eq!(player_round_state.airdrop_odds, 1);
+
+    //once the window has passed a new commitment replaces the old one
+    env.warp_slots(REVEAL_WINDOW + 1).await;
+    env.commit_purchase_keys(&alice, &alice_acc, sol(0.3), [8; 32])
+        .await
+        .unwrap();
+    let player_round_state = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(player_round_state.airdrop_stake, sol(0.3));
+    assert_eq!(player_round_state.airdrop_odds, 3);
+}
+
+#[tokio::test]
+async fn test_init_refuses_bad_reveal_window() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.randomness = RandomnessSource::CommitReveal;
+    params.airdrop.reveal_window = 0;
+    let err = env.init_game_with_params(params.clone()).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropConfig as u32);
+
+    //slot hashes past the sysvar's 512 slots of history can't be verified
+    params.airdrop.reveal_window = 513;
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidAirdropConfig as u32);
+}
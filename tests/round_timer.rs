@@ -0,0 +1,179 @@
+mod utils;
+
+use fomo3d::{
+    error::GameError,
+    state::{AntiSnipe, TimingMode},
+};
+use solana_program::clock::Clock;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+const INC_TIME_PER_KEY: i64 = 1;
+const MAX_TIME: i64 = 60 * 60;
+
+async fn now(env: &mut TestEnv) -> i64 {
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp
+}
+
+#[tokio::test]
+async fn test_keys_extend_end_time() {
+    let mut env = TestEnv::with_game(|params| {
+        params.round_inc_time_per_key = INC_TIME_PER_KEY;
+        params.round_max_time = MAX_TIME;
+        params.limiter.enabled = false;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    let end_time = env.round_state().await.end_time;
+
+    //a small purchase stays well under the ceiling
+    env.purchase_keys(&alice, &alice_acc, sol(0.01))
+        .await
+        .unwrap();
+    let round_state = env.round_state().await;
+    assert!(round_state.accum_keys as i64 * INC_TIME_PER_KEY < MAX_TIME);
+    assert_eq!(
+        round_state.end_time,
+        end_time + round_state.accum_keys as i64 * INC_TIME_PER_KEY
+    );
+}
+
+#[tokio::test]
+async fn test_end_time_never_exceeds_max_time_from_now() {
+    let mut env = TestEnv::with_game(|params| {
+        params.round_inc_time_per_key = INC_TIME_PER_KEY;
+        params.round_max_time = MAX_TIME;
+        params.limiter.enabled = false;
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    let now_1 = now(&mut env).await;
+    assert_eq!(env.round_state().await.end_time, now_1 + MAX_TIME);
+
+    //repeated purchases used to stack the capped delay on top of the previous end time
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    assert_eq!(env.round_state().await.end_time, now_1 + MAX_TIME);
+
+    //later on the ceiling moves along with the clock
+    env.warp_time(MAX_TIME / 2).await;
+    let now_2 = now(&mut env).await;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    assert_eq!(env.round_state().await.end_time, now_2 + MAX_TIME);
+
+    env.warp_time(MAX_TIME - 1).await;
+    let now_3 = now(&mut env).await;
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    assert_eq!(env.round_state().await.end_time, now_3 + MAX_TIME);
+}
+
+const SNIPE_WINDOW: i64 = 60;
+const SNIPE_MIN_TIME_LEFT: i64 = 120;
+
+#[tokio::test]
+async fn test_anti_snipe_extends_last_second_purchase() {
+    let mut env = TestEnv::with_game(|params| {
+        params.round_init_time = 600;
+        params.anti_snipe = AntiSnipe {
+            window: SNIPE_WINDOW,
+            min_time_left: SNIPE_MIN_TIME_LEFT,
+        };
+    })
+    .await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    //outside the window keys add no time (0s per key in tests)
+    let end_time = env.round_state().await.end_time;
+    env.purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap();
+    assert_eq!(env.round_state().await.end_time, end_time);
+
+    //10s before the end
+    let time_left = end_time - now(&mut env).await;
+    env.warp_time(time_left - 10).await;
+    env.purchase_keys(&bob, &bob_acc, sol(0.5)).await.unwrap();
+    let snipe_time = now(&mut env).await;
+    assert_eq!(
+        env.round_state().await.end_time,
+        snipe_time + SNIPE_MIN_TIME_LEFT
+    );
+}
+
+#[tokio::test]
+async fn test_anti_snipe_keeps_longer_time_left() {
+    let mut env = TestEnv::with_game(|params| {
+        params.round_init_time = 600;
+        params.anti_snipe = AntiSnipe {
+            window: SNIPE_WINDOW,
+            min_time_left: 30,
+        };
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    //50s left is inside the window, but already more than the 30s guaranteed
+    let end_time = env.round_state().await.end_time;
+    let time_left = end_time - now(&mut env).await;
+    env.warp_time(time_left - 50).await;
+    env.purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap();
+    assert_eq!(env.round_state().await.end_time, end_time);
+}
+
+#[tokio::test]
+async fn test_anti_snipe_refuses_min_time_above_max() {
+    let mut env = TestEnv::new().await;
+    let mut params = default_game_params(env.version);
+    params.anti_snipe = AntiSnipe {
+        window: SNIPE_WINDOW,
+        min_time_left: ROUND_MAX_TIME + 1,
+    };
+    let err = env.init_game_with_params(params).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::AboveThreshold as u32);
+}
+
+const INIT_SLOTS: i64 = 10;
+
+#[tokio::test]
+async fn test_slot_timing_measures_round_in_slots() {
+    let mut env = TestEnv::with_game(|params| {
+        params.timing_mode = TimingMode::Slot;
+        params.round_init_time = INIT_SLOTS;
+    })
+    .await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let clock: Clock = env.context.banks_client.get_sysvar().await.unwrap();
+    let round_state = env.round_state().await;
+    assert!(round_state.start_time <= clock.slot as i64);
+    assert_eq!(round_state.end_time, round_state.start_time + INIT_SLOTS);
+
+    //the wall clock moving on doesn't end the round
+    env.warp_time(ROUND_MAX_TIME).await;
+    env.purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap();
+
+    //slots do
+    env.warp_slots(INIT_SLOTS as u64 + 1).await;
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::AlreadyEnded as u32);
+    env.end_round(&alice.pubkey()).await.unwrap();
+    assert!(env.round_state().await.ended);
+}
@@ -0,0 +1,155 @@
+mod utils;
+
+use fomo3d::{error::GameError, math::curve::sol_received, state::PROFIT_PER_KEY_PRECISION};
+use solana_sdk::signature::Signer;
+use utils::*;
+
+//team 2 keeps the least in the pot
+const LOWEST_POT_PERCENT: u128 = 20;
+
+#[tokio::test]
+async fn test_sell_keys() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+
+    let round_before = env.round_state().await;
+    let alice_before = env.player_round_state(&alice.pubkey()).await;
+    let pending_f3d =
+        round_before.profit_per_key * alice_before.accum_keys / PROFIT_PER_KEY_PRECISION;
+    let to_sell = alice_before.accum_keys / 2;
+    let curve_value = sol_received(round_before.accum_keys, to_sell).unwrap();
+    let proceeds = curve_value * LOWEST_POT_PERCENT / 100;
+    let exit_fee = proceeds * EXIT_FEE_PERCENT as u128 / 100;
+
+    let balance_before = env.token_balance(&alice_acc).await;
+    env.sell_keys(&alice, &alice_acc, to_sell).await.unwrap();
+
+    //alice gets the proceeds less the fee, plus any f3d earned so far
+    assert_eq!(
+        env.token_balance(&alice_acc).await as u128,
+        balance_before as u128 + proceeds - exit_fee + pending_f3d
+    );
+    let alice_after = env.player_round_state(&alice.pubkey()).await;
+    assert_eq!(alice_after.accum_keys, alice_before.accum_keys - to_sell);
+    assert_eq!(alice_after.withdrawn_f3d, pending_f3d);
+    let round_after = env.round_state().await;
+    assert_eq!(round_after.accum_keys, round_before.accum_keys - to_sell);
+    assert_eq!(
+        round_after.curve_position,
+        round_before.curve_position - curve_value
+    );
+    assert_eq!(
+        round_after.accum_sol_by_team[1],
+        round_before.accum_sol_by_team[1] - curve_value
+    );
+    assert_eq!(
+        round_after.accum_sol_pot,
+        round_before.accum_sol_pot - (proceeds - exit_fee)
+    );
+    assert!(round_after.accum_f3d_share > round_before.accum_f3d_share);
+
+    //the exit fee goes to the other key holders, not the seller
+    let alice_f3d = round_after.profit_per_key * alice_after.accum_keys / PROFIT_PER_KEY_PRECISION
+        - alice_after.f3d_mask;
+    assert_eq!(alice_f3d, 0);
+    let bob_state = env.player_round_state(&bob.pubkey()).await;
+    let bob_f3d_before = round_before.profit_per_key * bob_state.accum_keys
+        / PROFIT_PER_KEY_PRECISION
+        - bob_state.f3d_mask;
+    let bob_f3d_after = round_after.profit_per_key * bob_state.accum_keys
+        / PROFIT_PER_KEY_PRECISION
+        - bob_state.f3d_mask;
+    assert!(bob_f3d_after > bob_f3d_before);
+}
+
+#[tokio::test]
+async fn test_sell_keys_refuses_more_than_owned() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+
+    let keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    let err = env
+        .sell_keys(&alice, &alice_acc, keys + 1)
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::InsufficientKeys as u32);
+}
+
+#[tokio::test]
+async fn test_sell_keys_round_trip_keeps_pot() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    let round_before = env.round_state().await;
+
+    //bob buys on the team keeping the least in the pot and sells straight back
+    //(all but the one key he has to keep as the leader)
+    let ix = env.purchase_keys_ix(&bob.pubkey(), &bob_acc, sol(1.0), 2, None);
+    env.process_tx(&[ix], &[&bob]).await.unwrap();
+    let keys = env.player_round_state(&bob.pubkey()).await.accum_keys;
+    env.sell_keys(&bob, &bob_acc, keys - 1).await.unwrap();
+
+    let round_after = env.round_state().await;
+    assert!(round_after.still_in_play >= round_before.still_in_play);
+    assert_eq!(round_after.accum_keys, round_before.accum_keys + 1);
+}
+
+#[tokio::test]
+async fn test_leader_cannot_sell_all_keys() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+
+    let keys = env.player_round_state(&bob.pubkey()).await.accum_keys;
+    let err = env.sell_keys(&bob, &bob_acc, keys).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::LeaderCannotSellAllKeys as u32);
+    env.sell_keys(&bob, &bob_acc, keys - 1).await.unwrap();
+    assert_eq!(env.round_state().await.lead_player_pk, bob.pubkey());
+
+    //anyone else can cash out completely
+    let keys = env.player_round_state(&alice.pubkey()).await.accum_keys;
+    env.sell_keys(&alice, &alice_acc, keys).await.unwrap();
+    assert_eq!(env.player_round_state(&alice.pubkey()).await.accum_keys, 0);
+}
+
+#[tokio::test]
+async fn test_sell_keys_refuses_after_round_ends() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+    env.wait_for_round_to_end().await;
+
+    let err = env.sell_keys(&alice, &alice_acc, 1).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::AlreadyEnded as u32);
+}
@@ -0,0 +1,93 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_strong_rng_requires_slot_hashes() {
+    let mut env = TestEnv::with_game(|params| params.strong_rng = true).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+
+    env.pass_slot_hashes = true;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    assert!(env.round_state().await.accum_keys > 0);
+}
+
+#[tokio::test]
+async fn test_strong_rng_requires_slot_hashes_on_reload() {
+    let mut env = TestEnv::with_game(|params| params.strong_rng = true).await;
+    env.pass_slot_hashes = true;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+    let ix = env.purchase_keys_ix(
+        &alice.pubkey(),
+        &alice_acc,
+        sol(1.0),
+        1,
+        Some(&bob.pubkey()),
+    );
+    env.process_tx(&[ix], &[&alice]).await.unwrap();
+
+    env.pass_slot_hashes = false;
+    let err = env.reload_keys(&bob, sol(0.05), 1).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+
+    env.pass_slot_hashes = true;
+    env.reload_keys(&bob, sol(0.05), 1).await.unwrap();
+    assert!(env.player_round_state(&bob.pubkey()).await.accum_keys > 0);
+}
+
+#[tokio::test]
+async fn test_slot_hashes_are_optional_otherwise() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    env.pass_slot_hashes = true;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+
+    //passed after the affiliate accounts, which still get picked up
+    let ix = env.purchase_keys_ix(
+        &alice.pubkey(),
+        &alice_acc,
+        sol(1.0),
+        1,
+        Some(&bob.pubkey()),
+    );
+    env.process_tx(&[ix], &[&alice]).await.unwrap();
+    assert_eq!(
+        env.player_round_state(&bob.pubkey()).await.accum_aff,
+        sol(0.1)
+    );
+}
+
+#[tokio::test]
+async fn test_slot_hashes_checked_where_parsed() {
+    let mut env = TestEnv::with_game(|params| params.strong_rng = true).await;
+    env.pass_slot_hashes = true;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+
+    //SlotHashes is passed, but not where it's read from
+    let mut ix = env.purchase_keys_ix(
+        &alice.pubkey(),
+        &alice_acc,
+        sol(1.0),
+        1,
+        Some(&bob.pubkey()),
+    );
+    let last = ix.accounts.len() - 1;
+    ix.accounts.swap(9, last);
+    let err = env.process_tx(&[ix], &[&alice]).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::WrongAccount as u32);
+}
@@ -0,0 +1,118 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_program_test::*;
+use solana_sdk::{compute_budget::ComputeBudgetInstruction, signature::Signer};
+use utils::*;
+
+/// Stands in for a contract gaming the airdrop - forwards whatever it gets to the game via CPI.
+/// The game's program account is passed last.
+fn proxy_process_instruction(
+    _program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let (game_program, forwarded) = accounts.split_last().unwrap();
+    let ix = Instruction {
+        program_id: *game_program.key,
+        accounts: forwarded
+            .iter()
+            .map(|acc| AccountMeta {
+                pubkey: *acc.key,
+                is_signer: acc.is_signer,
+                is_writable: acc.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+    invoke(&ix, accounts)
+}
+
+async fn top_level_env(proxy_id: Pubkey) -> TestEnv {
+    let mut env = TestEnv::new_with_setup(9, |program_test| {
+        program_test.add_program("proxy", proxy_id, processor!(proxy_process_instruction));
+    })
+    .await;
+    env.start_game(|params| params.top_level_purchases = true)
+        .await;
+    env.pass_instructions = true;
+    env
+}
+
+#[tokio::test]
+async fn test_top_level_purchase_goes_through() {
+    let mut env = top_level_env(Pubkey::new_unique()).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    //compute budget instructions can come after it
+    let ix = env.purchase_keys_ix(&alice.pubkey(), &alice_acc, sol(1.0), 1, None);
+    let budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(400_000);
+    env.process_tx(&[ix, budget_ix], &[&alice]).await.unwrap();
+    assert!(env.round_state().await.accum_keys > 0);
+
+    //the Instructions sysvar can't be left out
+    env.pass_instructions = false;
+    let err = env
+        .purchase_keys(&alice, &alice_acc, sol(0.5))
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::MissingAccount as u32);
+}
+
+#[tokio::test]
+async fn test_purchase_via_cpi_is_refused() {
+    let proxy_id = Pubkey::new_unique();
+    let mut env = top_level_env(proxy_id).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    let mut ix = env.purchase_keys_ix(&alice.pubkey(), &alice_acc, sol(1.0), 1, None);
+    ix.accounts
+        .push(AccountMeta::new_readonly(env.program_id, false));
+    ix.program_id = proxy_id;
+    let err = env.process_tx(&[ix], &[&alice]).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::NotTopLevel as u32);
+    assert_eq!(env.round_state().await.accum_keys, 0);
+}
+
+#[tokio::test]
+async fn test_purchase_followed_by_other_program_is_refused() {
+    let mut env = top_level_env(Pubkey::new_unique()).await;
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+
+    //anything after the purchase could check the airdrop outcome and fail the tx to undo it
+    let ix = env.purchase_keys_ix(&alice.pubkey(), &alice_acc, sol(1.0), 1, None);
+    let other_ix = system_instruction::transfer(&alice.pubkey(), &env.bob.pubkey(), 1);
+    let err = env
+        .process_tx(&[ix, other_ix], &[&alice])
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::UnexpectedInstruction as u32);
+    assert_eq!(env.round_state().await.accum_keys, 0);
+}
+
+#[tokio::test]
+async fn test_multiple_purchases_in_one_tx_are_refused() {
+    let mut env = top_level_env(Pubkey::new_unique()).await;
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+
+    let alice_ix = env.purchase_keys_ix(&alice.pubkey(), &alice_acc, sol(1.0), 1, None);
+    let bob_ix = env.purchase_keys_ix(&bob.pubkey(), &bob_acc, sol(1.0), 1, None);
+    let err = env
+        .process_tx(&[alice_ix, bob_ix], &[&alice, &bob])
+        .await
+        .unwrap_err();
+    assert_eq!(custom_error(err), GameError::MultiplePurchases as u32);
+    assert_eq!(env.round_state().await.accum_keys, 0);
+}
@@ -0,0 +1,1041 @@
+#![allow(dead_code)]
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use fomo3d::{
+    entrypoint::process_instruction,
+    instruction::{
+        GameInstruction, InitGameParams, PurchaseKeysParams, PurchaseOutcome, RegisterNameParams,
+        ReloadKeysParams, RevealAirdropParams, SellKeysParams, WithdrawParams,
+    },
+    state::{
+        AffiliateRules, AirdropConfig, AirdropTier, AntiSnipe, EndRoundBounty, FeeSplit, GameState,
+        IdState, Limiter, NameState, OracleResult, PlayerRoundState, PlayerState, PotSplit,
+        RandomnessSource, RoundState, TeamConfig, TimingMode, MAX_AIRDROP_TIERS,
+    },
+};
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    native_token::LAMPORTS_PER_SOL,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program, sysvar,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+
+//setting these low so that we can run tests
+//actual recommended numbers are 1h / 30s / 24h
+pub const ROUND_INIT_TIME: i64 = 2;
+pub const ROUND_INC_TIME_PER_KEY: i64 = 0;
+pub const ROUND_MAX_TIME: i64 = 24 * 60 * 60;
+pub const EXIT_FEE_PERCENT: u8 = 10;
+pub const REVEAL_WINDOW: u64 = 50;
+pub const NAME_FEE: u128 = LAMPORTS_PER_SOL as u128 / 100;
+
+//teams used by the original Fomo3D, in order: whale, bear, snek, bull
+pub fn team(fee_f3d: u8, fee_p3d: u8, pot_f3d: u8, pot_p3d: u8) -> TeamConfig {
+    TeamConfig {
+        fee_split: FeeSplit {
+            community: 2,
+            airdrop: 1,
+            next_round: 1,
+            affiliate: 10,
+            f3d: fee_f3d,
+            p3d: fee_p3d,
+            pot: 86 - fee_f3d - fee_p3d,
+        },
+        pot_split: PotSplit {
+            winner: 48,
+            community: 2,
+            next_round: 50 - pot_f3d - pot_p3d,
+            f3d: pot_f3d,
+            p3d: pot_p3d,
+        },
+    }
+}
+pub fn default_teams() -> Vec<TeamConfig> {
+    vec![
+        team(30, 6, 15, 10),
+        team(43, 0, 25, 0),
+        team(56, 10, 20, 20),
+        team(43, 8, 30, 10),
+    ]
+}
+
+pub fn sol(amount: f64) -> u128 {
+    (amount * LAMPORTS_PER_SOL as f64) as u128
+}
+
+//airdrop used by the original Fomo3D: 25/50/75% of the pot for 0.1+/1+/10+ sol
+pub fn default_airdrop() -> AirdropConfig {
+    let mut tiers = [AirdropTier::default(); MAX_AIRDROP_TIERS];
+    tiers[0] = AirdropTier {
+        min_stake: sol(0.1),
+        payout_percent: 25,
+    };
+    tiers[1] = AirdropTier {
+        min_stake: sol(1.0),
+        payout_percent: 50,
+    };
+    tiers[2] = AirdropTier {
+        min_stake: sol(10.0),
+        payout_percent: 75,
+    };
+    AirdropConfig {
+        min_stake: sol(0.1),
+        odds_step: 1,
+        reveal_window: REVEAL_WINDOW,
+        oracle: Pubkey::default(),
+        oracle_result: Pubkey::default(),
+        oracle_max_age: 0,
+        tier_count: 3,
+        tiers,
+    }
+}
+
+pub fn default_game_params(version: u64) -> InitGameParams {
+    InitGameParams {
+        version,
+        round_init_time: ROUND_INIT_TIME,
+        round_inc_time_per_key: ROUND_INC_TIME_PER_KEY,
+        round_max_time: ROUND_MAX_TIME,
+        anti_snipe: AntiSnipe::default(),
+        timing_mode: TimingMode::Timestamp,
+        exit_fee_percent: EXIT_FEE_PERCENT,
+        end_round_bounty: EndRoundBounty::default(),
+        second_tier_affiliate: 0,
+        affiliate_rules: AffiliateRules::default(),
+        name_fee: NAME_FEE,
+        teams: default_teams(),
+        limiter: Limiter {
+            enabled: true,
+            pot_threshold: sol(100.0),
+            player_cap: sol(1.0),
+        },
+        airdrop: default_airdrop(),
+        randomness: RandomnessSource::PseudoRng,
+        strong_rng: false,
+        top_level_purchases: false,
+        auto_settle: false,
+    }
+}
+
+/// Holds the test validator and all the accounts that participate in the game.
+/// Mirrors the setup done by `prepareTestEnv` in the js client.
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+    pub program_id: Pubkey,
+    pub version: u64,
+    pub round: u64,
+    pub game_creator: Keypair,
+    pub alice: Keypair,
+    pub bob: Keypair,
+    pub mint: Keypair,
+    pub decimals: u8,
+    pub alice_token_acc: Pubkey,
+    pub bob_token_acc: Pubkey,
+    //community & p3d accounts are assigned to bob - pretend he's the leader of both
+    pub com_wallet: Pubkey,
+    pub p3d_wallet: Pubkey,
+    //passed along with purchases when set, for games using Oracle randomness
+    pub oracle_result: Option<Pubkey>,
+    //passes SlotHashes along with purchases, for games using strong rng
+    pub pass_slot_hashes: bool,
+    //passes Instructions along with purchases, for games only allowing top-level purchases
+    pub pass_instructions: bool,
+    //winner-round state passed along with purchases when set, for games auto-settling expired rounds
+    pub settle_winner: Option<Pubkey>,
+}
+
+impl TestEnv {
+    pub async fn new() -> Self {
+        Self::new_with_decimals(9).await
+    }
+
+    pub async fn new_with_decimals(decimals: u8) -> Self {
+        Self::new_with_setup(decimals, |_| {}).await
+    }
+
+    /// Lets the caller register extra programs / accounts before the validator starts.
+    pub async fn new_with_setup(decimals: u8, setup: impl FnOnce(&mut ProgramTest)) -> Self {
+        let program_id = Pubkey::new_unique();
+        let mut program_test =
+            ProgramTest::new("fomo3d", program_id, processor!(process_instruction));
+        setup(&mut program_test);
+
+        let game_creator = Keypair::new();
+        let alice = Keypair::new();
+        let bob = Keypair::new();
+        for kp in [&game_creator, &alice, &bob] {
+            program_test.add_account(
+                kp.pubkey(),
+                Account::new(10 * LAMPORTS_PER_SOL, 0, &system_program::id()),
+            );
+        }
+
+        let context = program_test.start_with_context().await;
+        let mut env = TestEnv {
+            context,
+            program_id,
+            version: 1,
+            round: 0,
+            game_creator,
+            alice,
+            bob,
+            mint: Keypair::new(),
+            decimals,
+            alice_token_acc: Pubkey::default(),
+            bob_token_acc: Pubkey::default(),
+            com_wallet: Pubkey::default(),
+            p3d_wallet: Pubkey::default(),
+            oracle_result: None,
+            pass_slot_hashes: false,
+            pass_instructions: false,
+            settle_winner: None,
+        };
+
+        env.create_mint().await;
+        let bob_pk = env.bob.pubkey();
+        let alice_pk = env.alice.pubkey();
+        env.com_wallet = env.create_token_acc(&bob_pk, 0).await;
+        env.p3d_wallet = env.create_token_acc(&bob_pk, 0).await;
+        let amount = env.tokens(100.0) as u64;
+        env.alice_token_acc = env.create_token_acc(&alice_pk, amount).await;
+        env.bob_token_acc = env.create_token_acc(&bob_pk, amount).await;
+        env
+    }
+
+    // --------------------------------------- tx helpers
+
+    async fn sign_tx(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Transaction {
+        //a fresh blockhash each time so that repeated identical txs aren't deduplicated
+        let blockhash = self.context.get_new_latest_blockhash().await.unwrap();
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        )
+    }
+
+    pub async fn process_tx(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), TransactionError> {
+        let tx = self.sign_tx(instructions, signers).await;
+        self.context
+            .banks_client
+            .process_transaction(tx)
+            .await
+            .map_err(|e| e.unwrap())
+    }
+
+    /// Same as process_tx, but hands back whatever return data the transaction set.
+    pub async fn process_tx_with_return_data(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<Vec<u8>, TransactionError> {
+        let tx = self.sign_tx(instructions, signers).await;
+        let result = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(tx)
+            .await
+            .unwrap();
+        result.result?;
+        Ok(result
+            .metadata
+            .and_then(|metadata| metadata.return_data)
+            .map(|return_data| return_data.data)
+            .unwrap_or_default())
+    }
+
+    /// Amount in base units of the game's mint.
+    pub fn tokens(&self, amount: f64) -> u128 {
+        (amount * 10f64.powi(self.decimals as i32)) as u128
+    }
+
+    async fn create_mint(&mut self) {
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = spl_token::state::Mint::LEN;
+        let ixs = [
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &self.mint.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &self.mint.pubkey(),
+                &self.game_creator.pubkey(),
+                None,
+                self.decimals,
+            )
+            .unwrap(),
+        ];
+        let mint = clone_kp(&self.mint);
+        self.process_tx(&ixs, &[&mint]).await.unwrap();
+    }
+
+    pub async fn create_token_acc(&mut self, owner: &Pubkey, amount: u64) -> Pubkey {
+        let rent = self.context.banks_client.get_rent().await.unwrap();
+        let space = spl_token::state::Account::LEN;
+        let acc = Keypair::new();
+        let mut ixs = vec![
+            system_instruction::create_account(
+                &self.context.payer.pubkey(),
+                &acc.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &acc.pubkey(),
+                &self.mint.pubkey(),
+                owner,
+            )
+            .unwrap(),
+        ];
+        if amount > 0 {
+            ixs.push(
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    &self.mint.pubkey(),
+                    &acc.pubkey(),
+                    &self.game_creator.pubkey(),
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+            );
+        }
+        let game_creator = clone_kp(&self.game_creator);
+        let signers: &[&Keypair] = if amount > 0 {
+            &[&acc, &game_creator]
+        } else {
+            &[&acc]
+        };
+        self.process_tx(&ixs, signers).await.unwrap();
+        acc.pubkey()
+    }
+
+    /// Moves the on-chain clock forward by the passed number of seconds.
+    pub async fn warp_time(&mut self, seconds: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    /// Writes a stand-in for the account an oracle publishes its randomness to.
+    pub fn write_oracle_result(
+        &mut self,
+        result: &Pubkey,
+        oracle: &Pubkey,
+        seq: u64,
+        slot: u64,
+        randomness: [u8; 32],
+    ) {
+        let data = OracleResult {
+            seq,
+            slot,
+            randomness,
+        }
+        .try_to_vec()
+        .unwrap();
+        let mut account = Account::new(LAMPORTS_PER_SOL, data.len(), oracle);
+        account.data = data;
+        self.context.set_account(result, &account.into());
+    }
+
+    /// Moves the bank forward by the passed number of slots, recording slot hashes along the way.
+    pub async fn warp_slots(&mut self, slots: u64) {
+        let slot = self.context.banks_client.get_root_slot().await.unwrap();
+        self.context.warp_to_slot(slot + slots).unwrap();
+    }
+
+    pub async fn wait_for_round_to_end(&mut self) {
+        let round_state = self.round_state().await;
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        self.warp_time(round_state.end_time - clock.unix_timestamp + 1)
+            .await;
+    }
+
+    // --------------------------------------- pdas
+
+    pub fn game_state_pk(&self) -> Pubkey {
+        self.find_pda(&format!("game{}", self.version))
+    }
+
+    pub fn round_state_pk(&self, round: u64) -> Pubkey {
+        self.find_pda(&format!("round{}{}", round, self.version))
+    }
+
+    pub fn pot_pk(&self, round: u64) -> Pubkey {
+        self.find_pda(&format!("pot{}{}", round, self.version))
+    }
+
+    pub fn name_state_pk(&self, name: &str) -> Pubkey {
+        self.find_pda(&format!("nm{}/{}", self.version, name))
+    }
+
+    pub fn id_state_pk(&self, id: u64) -> Pubkey {
+        self.find_pda(&format!("id{}/{}", self.version, id))
+    }
+
+    pub fn player_round_state_pk(&self, player: &Pubkey, round: u64) -> Pubkey {
+        self.find_pda(&format!(
+            "pr{}{}{}",
+            &player.to_string()[..12],
+            round,
+            self.version
+        ))
+    }
+
+    pub fn player_state_pk(&self, player: &Pubkey) -> Pubkey {
+        self.find_pda(&format!("ps{}{}", &player.to_string()[..12], self.version))
+    }
+
+    fn find_pda(&self, seed: &str) -> Pubkey {
+        Pubkey::find_program_address(&[seed.as_bytes()], &self.program_id).0
+    }
+
+    // --------------------------------------- state getters
+
+    pub async fn account_data(&mut self, pk: &Pubkey) -> Vec<u8> {
+        self.context
+            .banks_client
+            .get_account(*pk)
+            .await
+            .unwrap()
+            .unwrap()
+            .data
+    }
+
+    pub async fn game_state(&mut self) -> GameState {
+        let pk = self.game_state_pk();
+        GameState::try_from_slice(&self.account_data(&pk).await).unwrap()
+    }
+
+    pub async fn name_state(&mut self, name: &str) -> NameState {
+        let pk = self.name_state_pk(name);
+        NameState::try_from_slice(&self.account_data(&pk).await).unwrap()
+    }
+
+    pub async fn id_state(&mut self, id: u64) -> IdState {
+        let pk = self.id_state_pk(id);
+        IdState::try_from_slice(&self.account_data(&pk).await).unwrap()
+    }
+
+    pub async fn round_state(&mut self) -> RoundState {
+        self.round_state_for(self.round).await
+    }
+
+    pub async fn round_state_for(&mut self, round: u64) -> RoundState {
+        let pk = self.round_state_pk(round);
+        RoundState::try_from_slice(&self.account_data(&pk).await).unwrap()
+    }
+
+    pub async fn player_round_state(&mut self, player: &Pubkey) -> PlayerRoundState {
+        self.player_round_state_for(player, self.round).await
+    }
+
+    pub async fn player_round_state_for(
+        &mut self,
+        player: &Pubkey,
+        round: u64,
+    ) -> PlayerRoundState {
+        let pk = self.player_round_state_pk(player, round);
+        PlayerRoundState::try_from_slice(&self.account_data(&pk).await).unwrap()
+    }
+
+    pub async fn player_state(&mut self, player: &Pubkey) -> PlayerState {
+        let pk = self.player_state_pk(player);
+        PlayerState::try_from_slice(&self.account_data(&pk).await).unwrap()
+    }
+
+    pub async fn token_balance(&mut self, pk: &Pubkey) -> u64 {
+        spl_token::state::Account::unpack(&self.account_data(pk).await)
+            .unwrap()
+            .amount
+    }
+
+    // --------------------------------------- instructions
+
+    /// Env with a game started off the default params, as tweaked by `configure`.
+    pub async fn with_game(configure: impl FnOnce(&mut InitGameParams)) -> Self {
+        let mut env = Self::new().await;
+        env.start_game(configure).await;
+        env
+    }
+
+    /// Inits the game off the default params, as tweaked by `configure`, and its first round.
+    pub async fn start_game(&mut self, configure: impl FnOnce(&mut InitGameParams)) {
+        let mut params = default_game_params(self.version);
+        configure(&mut params);
+        self.init_game_with_params(params).await.unwrap();
+        self.init_round(1).await.unwrap();
+    }
+
+    pub async fn init_game(&mut self) -> Result<(), TransactionError> {
+        self.init_game_with_params(default_game_params(self.version))
+            .await
+    }
+
+    pub async fn init_game_with_params(
+        &mut self,
+        params: InitGameParams,
+    ) -> Result<(), TransactionError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.game_creator.pubkey(), true),
+                AccountMeta::new(self.game_state_pk(), false),
+                AccountMeta::new_readonly(self.com_wallet, false),
+                AccountMeta::new_readonly(self.p3d_wallet, false),
+                AccountMeta::new_readonly(self.mint.pubkey(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: GameInstruction::InitializeGame(params)
+                .try_to_vec()
+                .unwrap(),
+        };
+        let game_creator = clone_kp(&self.game_creator);
+        self.process_tx(&[ix], &[&game_creator]).await
+    }
+
+    pub async fn init_round(&mut self, round: u64) -> Result<(), TransactionError> {
+        self.round = round;
+        let mut accounts = vec![
+            AccountMeta::new(self.game_creator.pubkey(), true),
+            AccountMeta::new(self.game_state_pk(), false),
+            AccountMeta::new(self.round_state_pk(round), false),
+            AccountMeta::new(self.pot_pk(round), false),
+            AccountMeta::new_readonly(self.mint.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ];
+        if round > 1 {
+            accounts.push(AccountMeta::new(self.round_state_pk(round - 1), false));
+            accounts.push(AccountMeta::new(self.pot_pk(round - 1), false));
+        }
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: GameInstruction::InitializeRound.try_to_vec().unwrap(),
+        };
+        let game_creator = clone_kp(&self.game_creator);
+        self.process_tx(&[ix], &[&game_creator]).await
+    }
+
+    pub fn purchase_keys_ix(
+        &self,
+        buyer: &Pubkey,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+        team: u8,
+        affiliate: Option<&Pubkey>,
+    ) -> Instruction {
+        self.commit_purchase_keys_ix(
+            buyer,
+            buyer_token_acc,
+            sol_to_be_added,
+            team,
+            affiliate,
+            [0; 32],
+        )
+    }
+
+    pub fn commit_purchase_keys_ix(
+        &self,
+        buyer: &Pubkey,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+        team: u8,
+        affiliate: Option<&Pubkey>,
+        airdrop_commitment: [u8; 32],
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new(*buyer, true),
+            AccountMeta::new_readonly(self.game_state_pk(), false),
+            AccountMeta::new(self.round_state_pk(self.round), false),
+            AccountMeta::new(self.player_round_state_pk(buyer, self.round), false),
+            AccountMeta::new(self.pot_pk(self.round), false),
+            AccountMeta::new(*buyer_token_acc, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.player_state_pk(buyer), false),
+        ];
+        if let Some(affiliate) = affiliate {
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(affiliate, self.round),
+                false,
+            ));
+            accounts.push(AccountMeta::new_readonly(*affiliate, false));
+            accounts.push(AccountMeta::new(self.player_state_pk(affiliate), false));
+        }
+        if let Some(oracle_result) = self.oracle_result {
+            accounts.push(AccountMeta::new_readonly(oracle_result, false));
+        }
+        if self.pass_slot_hashes {
+            accounts.push(AccountMeta::new_readonly(sysvar::slot_hashes::id(), false));
+        }
+        if self.pass_instructions {
+            accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+        }
+        if let Some(winner) = self.settle_winner {
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(&winner, self.round),
+                false,
+            ));
+        }
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: GameInstruction::PurchaseKeys(PurchaseKeysParams {
+                sol_to_be_added,
+                team,
+                airdrop_commitment,
+            })
+            .try_to_vec()
+            .unwrap(),
+        }
+    }
+
+    pub async fn purchase_keys(
+        &mut self,
+        buyer: &Keypair,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+    ) -> Result<(), TransactionError> {
+        let ix = self.purchase_keys_ix(&buyer.pubkey(), buyer_token_acc, sol_to_be_added, 1, None);
+        self.process_tx(&[ix], &[buyer]).await
+    }
+
+    /// Same as purchase_keys, but hands back what the purchase did.
+    pub async fn purchase_keys_outcome(
+        &mut self,
+        buyer: &Keypair,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+    ) -> Result<PurchaseOutcome, TransactionError> {
+        let ix = self.purchase_keys_ix(&buyer.pubkey(), buyer_token_acc, sol_to_be_added, 1, None);
+        let data = self.process_tx_with_return_data(&[ix], &[buyer]).await?;
+        Ok(PurchaseOutcome::try_from_slice(&data).unwrap())
+    }
+
+    /// Refers to the affiliate by a code (their name / id state) instead of their account.
+    pub async fn purchase_keys_via_code(
+        &mut self,
+        buyer: &Keypair,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+        affiliate: &Pubkey,
+        code: &Pubkey,
+    ) -> Result<(), TransactionError> {
+        let mut ix = self.purchase_keys_ix(
+            &buyer.pubkey(),
+            buyer_token_acc,
+            sol_to_be_added,
+            1,
+            Some(affiliate),
+        );
+        ix.accounts[10].pubkey = *code;
+        self.process_tx(&[ix], &[buyer]).await
+    }
+
+    /// Also passes the affiliate's own affiliate, for games paying a second affiliate tier.
+    pub async fn purchase_keys_two_tier(
+        &mut self,
+        buyer: &Keypair,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+        affiliate: &Pubkey,
+        second_tier: &Pubkey,
+    ) -> Result<(), TransactionError> {
+        let mut ix = self.purchase_keys_ix(
+            &buyer.pubkey(),
+            buyer_token_acc,
+            sol_to_be_added,
+            1,
+            Some(affiliate),
+        );
+        let second_tier_round_state = self.player_round_state_pk(second_tier, self.round);
+        let second_tier_player_state = self.player_state_pk(second_tier);
+        ix.accounts
+            .insert(12, AccountMeta::new(second_tier_round_state, false));
+        ix.accounts
+            .insert(13, AccountMeta::new(second_tier_player_state, false));
+        self.process_tx(&[ix], &[buyer]).await
+    }
+
+    pub fn reload_keys_ix(
+        &self,
+        player: &Pubkey,
+        sol_to_be_added: u128,
+        team: u8,
+        reload_from_round: u64,
+        affiliate: Option<&Pubkey>,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new(*player, true),
+            AccountMeta::new_readonly(self.game_state_pk(), false),
+            AccountMeta::new(self.round_state_pk(self.round), false),
+            AccountMeta::new(self.player_round_state_pk(player, self.round), false),
+            AccountMeta::new(self.pot_pk(self.round), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.player_state_pk(player), false),
+        ];
+        if reload_from_round != self.round {
+            accounts.push(AccountMeta::new_readonly(
+                self.round_state_pk(reload_from_round),
+                false,
+            ));
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(player, reload_from_round),
+                false,
+            ));
+            accounts.push(AccountMeta::new(self.pot_pk(reload_from_round), false));
+        }
+        if let Some(affiliate) = affiliate {
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(affiliate, self.round),
+                false,
+            ));
+            accounts.push(AccountMeta::new_readonly(*affiliate, false));
+            accounts.push(AccountMeta::new(self.player_state_pk(affiliate), false));
+        }
+        if let Some(oracle_result) = self.oracle_result {
+            accounts.push(AccountMeta::new_readonly(oracle_result, false));
+        }
+        if self.pass_slot_hashes {
+            accounts.push(AccountMeta::new_readonly(sysvar::slot_hashes::id(), false));
+        }
+        if self.pass_instructions {
+            accounts.push(AccountMeta::new_readonly(sysvar::instructions::id(), false));
+        }
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: GameInstruction::ReloadKeys(ReloadKeysParams {
+                sol_to_be_added,
+                team,
+                reload_from_round,
+                airdrop_commitment: [0; 32],
+            })
+            .try_to_vec()
+            .unwrap(),
+        }
+    }
+
+    pub async fn reload_keys(
+        &mut self,
+        player: &Keypair,
+        sol_to_be_added: u128,
+        reload_from_round: u64,
+    ) -> Result<(), TransactionError> {
+        let ix = self.reload_keys_ix(
+            &player.pubkey(),
+            sol_to_be_added,
+            1,
+            reload_from_round,
+            None,
+        );
+        self.process_tx(&[ix], &[player]).await
+    }
+
+    /// Buys keys committing to hash(secret) for the airdrop lottery.
+    pub async fn commit_purchase_keys(
+        &mut self,
+        buyer: &Keypair,
+        buyer_token_acc: &Pubkey,
+        sol_to_be_added: u128,
+        secret: [u8; 32],
+    ) -> Result<(), TransactionError> {
+        let commitment = solana_program::keccak::hash(&secret).to_bytes();
+        let ix = self.commit_purchase_keys_ix(
+            &buyer.pubkey(),
+            buyer_token_acc,
+            sol_to_be_added,
+            1,
+            None,
+            commitment,
+        );
+        self.process_tx(&[ix], &[buyer]).await
+    }
+
+    /// Settles the player's pending airdrop entry, on the oracle's result if one is set.
+    pub async fn reveal_airdrop(
+        &mut self,
+        player: &Keypair,
+        secret: [u8; 32],
+    ) -> Result<(), TransactionError> {
+        let randomness = self.oracle_result.unwrap_or_else(sysvar::slot_hashes::id);
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(player.pubkey(), true),
+                AccountMeta::new_readonly(self.game_state_pk(), false),
+                AccountMeta::new(self.round_state_pk(self.round), false),
+                AccountMeta::new(
+                    self.player_round_state_pk(&player.pubkey(), self.round),
+                    false,
+                ),
+                AccountMeta::new_readonly(randomness, false),
+            ],
+            data: GameInstruction::RevealAirdrop(RevealAirdropParams { secret })
+                .try_to_vec()
+                .unwrap(),
+        };
+        self.process_tx(&[ix], &[player]).await
+    }
+
+    pub async fn sell_keys(
+        &mut self,
+        player: &Keypair,
+        player_token_acc: &Pubkey,
+        keys_to_be_sold: u128,
+    ) -> Result<(), TransactionError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(player.pubkey(), true),
+                AccountMeta::new_readonly(self.game_state_pk(), false),
+                AccountMeta::new(self.round_state_pk(self.round), false),
+                AccountMeta::new(
+                    self.player_round_state_pk(&player.pubkey(), self.round),
+                    false,
+                ),
+                AccountMeta::new(self.pot_pk(self.round), false),
+                AccountMeta::new(*player_token_acc, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data: GameInstruction::SellKeys(SellKeysParams { keys_to_be_sold })
+                .try_to_vec()
+                .unwrap(),
+        };
+        self.process_tx(&[ix], &[player]).await
+    }
+
+    pub async fn withdraw_sol(
+        &mut self,
+        player: &Keypair,
+        player_token_acc: &Pubkey,
+    ) -> Result<(), TransactionError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(player.pubkey(), true),
+                AccountMeta::new_readonly(self.game_state_pk(), false),
+                AccountMeta::new_readonly(self.round_state_pk(self.round), false),
+                AccountMeta::new(
+                    self.player_round_state_pk(&player.pubkey(), self.round),
+                    false,
+                ),
+                AccountMeta::new(self.pot_pk(self.round), false),
+                AccountMeta::new(*player_token_acc, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new(self.player_state_pk(&player.pubkey()), false),
+            ],
+            data: GameInstruction::WithdrawSol(WithdrawParams {
+                withdraw_for_round: self.round,
+            })
+            .try_to_vec()
+            .unwrap(),
+        };
+        self.process_tx(&[ix], &[player]).await
+    }
+
+    pub async fn end_round(&mut self, winner: &Pubkey) -> Result<(), TransactionError> {
+        self.end_round_by(None, Some(winner)).await
+    }
+
+    pub async fn end_empty_round(&mut self) -> Result<(), TransactionError> {
+        self.end_round_by(None, None).await
+    }
+
+    /// Caller is only passed if they're collecting an end-round bounty.
+    pub async fn end_round_by(
+        &mut self,
+        caller: Option<&Keypair>,
+        winner: Option<&Pubkey>,
+    ) -> Result<(), TransactionError> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.game_state_pk(), false),
+            AccountMeta::new(self.round_state_pk(self.round), false),
+        ];
+        if let Some(winner) = winner {
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(winner, self.round),
+                false,
+            ));
+        }
+        let mut signers = vec![];
+        if let Some(caller) = caller {
+            accounts.push(AccountMeta::new(caller.pubkey(), true));
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(&caller.pubkey(), self.round),
+                false,
+            ));
+            accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+            accounts.push(AccountMeta::new(
+                self.player_state_pk(&caller.pubkey()),
+                false,
+            ));
+            signers.push(caller);
+        }
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: GameInstruction::EndRound.try_to_vec().unwrap(),
+        };
+        self.process_tx(&[ix], &signers).await
+    }
+
+    pub async fn end_and_advance(
+        &mut self,
+        winner: Option<&Pubkey>,
+    ) -> Result<(), TransactionError> {
+        let (round, next_round) = (self.round, self.round + 1);
+        let mut accounts = vec![
+            AccountMeta::new(self.game_creator.pubkey(), true),
+            AccountMeta::new(self.game_state_pk(), false),
+            AccountMeta::new(self.round_state_pk(round), false),
+            AccountMeta::new(self.pot_pk(round), false),
+            AccountMeta::new(self.round_state_pk(next_round), false),
+            AccountMeta::new(self.pot_pk(next_round), false),
+            AccountMeta::new_readonly(self.mint.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ];
+        if let Some(winner) = winner {
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(winner, round),
+                false,
+            ));
+        }
+        //the funder collects any end-round bounty
+        if self.game_state().await.end_round_bounty.is_enabled() {
+            let funder = self.game_creator.pubkey();
+            accounts.push(AccountMeta::new(
+                self.player_round_state_pk(&funder, round),
+                false,
+            ));
+            accounts.push(AccountMeta::new(self.player_state_pk(&funder), false));
+        }
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: GameInstruction::EndAndAdvance.try_to_vec().unwrap(),
+        };
+        let game_creator = clone_kp(&self.game_creator);
+        self.process_tx(&[ix], &[&game_creator]).await?;
+        self.round = next_round;
+        Ok(())
+    }
+
+    /// Name is passed as typed, but the name state is looked up by its normalized form.
+    pub async fn register_name(
+        &mut self,
+        player: &Keypair,
+        player_token_acc: &Pubkey,
+        name: &str,
+    ) -> Result<(), TransactionError> {
+        let next_id = self.game_state().await.name_count + 1;
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(player.pubkey(), true),
+                AccountMeta::new(self.game_state_pk(), false),
+                AccountMeta::new(self.round_state_pk(self.round), false),
+                AccountMeta::new(self.name_state_pk(&name.to_lowercase()), false),
+                AccountMeta::new(self.id_state_pk(next_id), false),
+                AccountMeta::new(self.pot_pk(self.round), false),
+                AccountMeta::new(*player_token_acc, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data: GameInstruction::RegisterName(RegisterNameParams {
+                name: name.to_string(),
+            })
+            .try_to_vec()
+            .unwrap(),
+        };
+        self.process_tx(&[ix], &[player]).await
+    }
+
+    pub async fn withdraw_com(&mut self, signer: &Keypair) -> Result<(), TransactionError> {
+        let ix = Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.game_state_pk(), false),
+                AccountMeta::new(self.round_state_pk(self.round), false),
+                AccountMeta::new(self.pot_pk(self.round), false),
+                AccountMeta::new(self.com_wallet, false),
+                AccountMeta::new_readonly(signer.pubkey(), true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data: GameInstruction::WithdrawCommunityRewards(WithdrawParams {
+                withdraw_for_round: self.round,
+            })
+            .try_to_vec()
+            .unwrap(),
+        };
+        self.process_tx(&[ix], &[signer]).await
+    }
+
+    pub fn withdraw_p3d_ix(&self, p3d_wallet: &Pubkey, signer: &Pubkey) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.game_state_pk(), false),
+                AccountMeta::new(self.round_state_pk(self.round), false),
+                AccountMeta::new(self.pot_pk(self.round), false),
+                AccountMeta::new(*p3d_wallet, false),
+                AccountMeta::new_readonly(*signer, true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data: GameInstruction::WithdrawP3DRewards(WithdrawParams {
+                withdraw_for_round: self.round,
+            })
+            .try_to_vec()
+            .unwrap(),
+        }
+    }
+
+    pub async fn withdraw_p3d(&mut self, signer: &Keypair) -> Result<(), TransactionError> {
+        let ix = self.withdraw_p3d_ix(&self.p3d_wallet, &signer.pubkey());
+        self.process_tx(&[ix], &[signer]).await
+    }
+}
+
+/// Unpacks the custom program error code out of a failed transaction.
+pub fn custom_error(err: TransactionError) -> u32 {
+    match err {
+        TransactionError::InstructionError(
+            _,
+            solana_program::instruction::InstructionError::Custom(code),
+        ) => code,
+        other => panic!("expected a custom program error, got {:?}", other),
+    }
+}
+
+/// Keypairs aren't Clone - this is the cheapest way to hand out a copy to a tx.
+pub fn clone_kp(kp: &Keypair) -> Keypair {
+    Keypair::from_bytes(&kp.to_bytes()).unwrap()
+}
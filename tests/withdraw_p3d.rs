@@ -0,0 +1,92 @@
+mod utils;
+
+use fomo3d::error::GameError;
+use solana_sdk::signature::Signer;
+use utils::*;
+
+#[tokio::test]
+async fn test_withdraw_p3d_before_round_ends() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let (alice_acc, bob_acc) = (env.alice_token_acc, env.bob_token_acc);
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.purchase_keys(&bob, &bob_acc, sol(1.0)).await.unwrap();
+
+    //team bear has 0% p3d fee split, but with no affiliates the 10% affiliate share goes to p3d
+    let expected = sol(0.1) * 2;
+    let round_state = env.round_state().await;
+    assert_eq!(round_state.accum_p3d_share, expected);
+
+    let p3d_wallet = env.p3d_wallet;
+    env.withdraw_p3d(&bob).await.unwrap();
+    assert_eq!(env.token_balance(&p3d_wallet).await as u128, expected);
+    let pot = env.pot_pk(1);
+    assert_eq!(env.token_balance(&pot).await as u128, sol(2.0) - expected);
+    assert_eq!(env.round_state().await.withdrawn_p3d, expected);
+
+    //2nd attempt does not move more out
+    env.withdraw_p3d(&bob).await.unwrap();
+    assert_eq!(env.token_balance(&p3d_wallet).await as u128, expected);
+    assert_eq!(env.round_state().await.withdrawn_p3d, expected);
+}
+
+#[tokio::test]
+async fn test_withdraw_p3d_after_round_ends() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+    env.wait_for_round_to_end().await;
+    env.end_round(&alice.pubkey()).await.unwrap();
+
+    let round_state = env.round_state().await;
+    assert!(round_state.ended);
+    let bob = clone_kp(&env.bob);
+    let p3d_wallet = env.p3d_wallet;
+    env.withdraw_p3d(&bob).await.unwrap();
+    assert_eq!(
+        env.token_balance(&p3d_wallet).await as u128,
+        round_state.accum_p3d_share
+    );
+}
+
+#[tokio::test]
+async fn test_withdraw_p3d_refuses_wrong_wallet() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let (alice, bob) = (clone_kp(&env.alice), clone_kp(&env.bob));
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+
+    //bob owns the community wallet too, but it's not the one registered for p3d
+    let ix = env.withdraw_p3d_ix(&env.com_wallet, &bob.pubkey());
+    let err = env.process_tx(&[ix], &[&bob]).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::WrongAccount as u32);
+}
+
+#[tokio::test]
+async fn test_withdraw_p3d_refuses_non_owner() {
+    let mut env = TestEnv::new().await;
+    env.init_game().await.unwrap();
+    env.init_round(1).await.unwrap();
+    let alice = clone_kp(&env.alice);
+    let alice_acc = env.alice_token_acc;
+    env.purchase_keys(&alice, &alice_acc, sol(1.0))
+        .await
+        .unwrap();
+
+    let err = env.withdraw_p3d(&alice).await.unwrap_err();
+    assert_eq!(custom_error(err), GameError::InvalidOwner as u32);
+    assert_eq!(env.round_state().await.withdrawn_p3d, 0);
+}